@@ -0,0 +1,25 @@
+const TOKEN_KEY = "jilo-token";
+
+/** Call the admin api with the token of the logged in user */
+export async function apiFetch(path: string, init: RequestInit = {}): Promise<Response> {
+  const headers = new Headers(init.headers);
+  const token = localStorage.getItem(TOKEN_KEY);
+  if (token) {
+    headers.append("authorization", `Bearer ${token}`);
+  }
+  return fetch(`/api/v1${path}`, { ...init, headers });
+}
+
+/** Open a session, returns whether the credentials were accepted */
+export async function login(name: string, password: string): Promise<boolean> {
+  const headers = new Headers();
+  headers.append("content-type", "application/json");
+  const body = JSON.stringify({ name, password });
+  const response = await fetch("/api/v1/login", { method: "POST", body, headers });
+  if (!response.ok) {
+    return false;
+  }
+  const session = await response.json();
+  localStorage.setItem(TOKEN_KEY, session.token);
+  return true;
+}
@@ -1,7 +1,8 @@
 import { html, css, LitElement } from "lit";
 import { customElement, state } from "lit/decorators.js";
 import { repeat } from "lit/directives/repeat.js";
-import { Pages } from "./models";
+import { ApiError, Pages, Reference, TemplateDefinition } from "./models";
+import { apiFetch, login } from "./api";
 
 /**
  * An example element.
@@ -23,6 +24,12 @@ export class JiloPageList extends LitElement {
   @state() pages: Pages[] = [];
   @state() isLoading: boolean = true;
   @state() newPageName: string = "";
+  @state() error: string = "";
+  @state() pageTemplates: string[] = [];
+  @state() newPageTemplate: string = "";
+  @state() needsLogin: boolean = false;
+  @state() loginName: string = "";
+  @state() loginPassword: string = "";
 
   connectedCallback(): void {
     super.connectedCallback();
@@ -32,42 +39,95 @@ export class JiloPageList extends LitElement {
 
   async fetchAllPages() {
     try {
-      const response = await fetch("/api/v1/pages");
+      const response = await apiFetch("/pages");
+      if (response.status === 401) {
+        this.needsLogin = true;
+        return;
+      }
       const json = await response.json();
       this.pages = json;
+      await this.fetchPageTemplates();
       this.isLoading = false;
     } catch (error) {
       console.error(error);
     }
   }
 
+  async fetchPageTemplates() {
+    const response = await apiFetch("/templates?kind=page");
+    if (!response.ok) {
+      return;
+    }
+    const templates: Record<string, TemplateDefinition> = await response.json();
+    this.pageTemplates = Object.keys(templates);
+    if (!this.pageTemplates.includes(this.newPageTemplate)) {
+      this.newPageTemplate = this.pageTemplates[0] ?? "";
+    }
+  }
+
   private async _onDelete(event: MouseEvent) {
     const id = (event.target as Element).getAttribute("data-page")
-    await fetch(`/api/v1/pages/${id}`, {method: "DELETE"})
-    await this.fetchAllPages()
+    const response = await apiFetch(`/pages/${id}`, {method: "DELETE"})
+    if (!response.ok) {
+      this.error = ((await response.json()) as ApiError).message
+      return
+    }
+    const {dangling_references}: {dangling_references: Reference[]} = await response.json()
+    this.error = dangling_references
+      .map((reference) => `${reference.name} still links to the deleted page in ${reference.field}`)
+      .join(", ")
+    this.pages = this.pages.filter((page) => page.id !== id)
   }
 
   private async _onCreate() {
-    const body = JSON.stringify({name: this.newPageName, template: "/pages/article"})
+    const body = JSON.stringify({name: this.newPageName, template: this.newPageTemplate})
     const headers = new Headers()
-    headers.append("content-type", "text/json")
-    await fetch(`/api/v1/pages`, {method: "POST", body, headers})
+    headers.append("content-type", "application/json")
+    const response = await apiFetch(`/pages`, {method: "POST", body, headers})
+    if (!response.ok) {
+      this.error = ((await response.json()) as ApiError).message
+      return
+    }
+    const page: Pages = await response.json()
+    this.error = ""
     this.newPageName = "";
-    await this.fetchAllPages()
+    this.pages = [...this.pages, page]
   }
 
 
+  private async _onLogin() {
+    if (await login(this.loginName, this.loginPassword)) {
+      this.needsLogin = false;
+      this.loginPassword = "";
+      await this.fetchAllPages()
+    }
+  }
+
   private _onInput(event: InputEvent) {
     this.newPageName = (event.target as HTMLInputElement).value
   }
 
   render() {
+    if (this.needsLogin) {
+      return html`
+        <div>
+          <input type="text" placeholder="name" @input=${(e: InputEvent) => this.loginName = (e.target as HTMLInputElement).value} />
+          <input type="password" placeholder="password" @input=${(e: InputEvent) => this.loginPassword = (e.target as HTMLInputElement).value} />
+          <button @click=${this._onLogin}>Login</button>
+        </div>
+      `;
+    }
+
     const loadingScreen = html`<span>Loading...</span>`;
 
     const pageFetched = html`
       <div>
         <input type="text" @input=${this._onInput} .value=${this.newPageName} />
+        <select @change=${(e: Event) => this.newPageTemplate = (e.target as HTMLSelectElement).value}>
+          ${this.pageTemplates.map((name) => html`<option value=${name} ?selected=${name === this.newPageTemplate}>${name}</option>`)}
+        </select>
         <button @click=${this._onCreate}>Create</button>
+        ${this.error ? html`<p class="error">${this.error}</p>` : ""}
         <ul>
           ${repeat(
             this.pages,
@@ -1,4 +1,103 @@
 export type Pages = {
   id: String;
   name: String;
+  slug: String;
+  parent: String | null;
+  order: number;
+  path: String;
+  status: "draft" | "published" | "modified";
+  publish_at: string | null;
+  unpublish_at: string | null;
+  access: AccessEntry[];
+};
+
+export type AccessEntry = ({ user: string } | { group: string }) & {
+  rights: ("view" | "edit" | "publish" | "delete")[];
+};
+
+/** Body of every error answered by the admin api */
+export type ApiError = {
+  code: string;
+  message: string;
+  details?: unknown;
+};
+
+export type Module = {
+  template: string;
+  fields: Record<string, unknown>;
+  areas: Record<string, Module[]>;
+};
+
+export type AreaRule =
+  | { accept: "all" }
+  | { accept: "with" | "without"; components: string[] };
+
+/** Kind of value a field holds, a select lists its options, lists and
+ * objects declare the fields of their items */
+export type FieldKind =
+  | "string"
+  | "text"
+  | "markdown"
+  | "rich_text"
+  | "number"
+  | "boolean"
+  | "date"
+  | "datetime"
+  | "url"
+  | "email"
+  | "color"
+  | "image"
+  | "file"
+  | { select: string[] }
+  | { page_ref: { templates: string[]; fields: string[] } }
+  | { list: Record<string, FieldDefinition> }
+  | { object: Record<string, FieldDefinition> };
+
+/** A declared field, the rules its value follows and its editor texts */
+export type FieldDefinition = {
+  kind: FieldKind;
+  required: boolean;
+  min?: number;
+  max?: number;
+  min_length?: number;
+  max_length?: number;
+  pattern?: string;
+  default?: unknown;
+  label?: string;
+  help?: string;
+};
+
+/** Fields of a template and the rules of its areas */
+export type TemplateDefinition = {
+  kind: "page" | "component";
+  fields: Record<string, FieldDefinition>;
+  areas: Record<string, { rule: AreaRule; accepted: string[] }>;
+};
+
+/** Answer of `GET /pages/{id}`, a page with its whole module tree */
+export type PageContent = Pages & {
+  module: Module;
+  templates: Record<string, TemplateDefinition>;
+};
+
+/** A field of a page still holding the id of a deleted page */
+export type Reference = {
+  page: string;
+  name: string;
+  module: string | null;
+  field: string;
+};
+
+/** A file of the media library, served at `/media/{file}` */
+export type Media = {
+  id: string;
+  name: string;
+  file: string;
+  mime: string;
+  size: number;
+  width: number | null;
+  height: number | null;
+  alt: string;
+  uploaded: string;
+  author: string | null;
 };
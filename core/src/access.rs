@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    account::{Role, User},
+    error::{Error, Result},
+    store::Store,
+};
+
+/// What a user may do on a page
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Right {
+    View,
+    Edit,
+    Publish,
+    Delete,
+}
+
+/// Who an access entry is about, written `user: name` or `group: name`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Subject {
+    User(String),
+    Group(String),
+}
+
+/// Rights granted on a page and on the pages under it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessEntry {
+    #[serde(flatten)]
+    pub subject: Subject,
+    pub rights: Vec<Right>,
+}
+
+impl AccessEntry {
+    fn grants(&self, user: &User, right: Right) -> bool {
+        let concerned = match &self.subject {
+            Subject::User(name) => *name == user.name,
+            Subject::Group(group) => user.groups.contains(group),
+        };
+        concerned && self.rights.contains(&right)
+    }
+}
+
+/// Entries of the closest page having some, the page itself or an ancestor,
+/// none when no page up to the root has any
+fn closest_access(store: &dyn Store, page_id: &str) -> Result<Vec<AccessEntry>> {
+    let mut visited = vec![];
+    let mut current = Some(page_id.to_owned());
+    while let Some(id) = current {
+        if visited.contains(&id) {
+            return Err(Error::PageCycle);
+        }
+        let page = store.get_page_info(&id).ok_or(Error::PageNotFound)?;
+        if !page.access.is_empty() {
+            return Ok(page.access);
+        }
+        visited.push(id);
+        current = page.parent;
+    }
+    Ok(vec![])
+}
+
+/// Whether the user has the right on the page. The entries of the closest
+/// page having some, the page itself or an ancestor, apply; pages without
+/// any up to the root are open to every user. Admins have every right.
+pub(crate) fn is_allowed(
+    store: &dyn Store,
+    user: &User,
+    page_id: &str,
+    right: Right,
+) -> Result<bool> {
+    if user.role == Role::Admin {
+        return Ok(true);
+    }
+    let access = closest_access(store, page_id)?;
+    Ok(access.is_empty() || access.iter().any(|entry| entry.grants(user, right)))
+}
+
+pub(crate) fn check(store: &dyn Store, user: &User, page_id: &str, right: Right) -> Result<()> {
+    match is_allowed(store, user, page_id, right)? {
+        true => Ok(()),
+        false => Err(Error::Forbidden),
+    }
+}
+
+/// Check the user has a right not tied to a page, like deleting a media. It
+/// is held by admins and by users every restricted page grants it to, a
+/// user limited to parts of the site does not get it.
+pub(crate) fn check_site(store: &dyn Store, user: &User, right: Right) -> Result<()> {
+    if user.role == Role::Admin {
+        return Ok(());
+    }
+    let allowed = store.summary().iter().all(|page| {
+        page.access.is_empty() || page.access.iter().any(|entry| entry.grants(user, right))
+    });
+    match allowed {
+        true => Ok(()),
+        false => Err(Error::Forbidden),
+    }
+}
+
+/// Check the user may move the page under `parent`, or to the root of the
+/// site when `None`. A page without entries of its own takes those of its
+/// new ancestors, so only admins may move it where they differ: it would
+/// lose its restrictions or get others.
+pub(crate) fn check_move(
+    store: &dyn Store,
+    user: &User,
+    page_id: &str,
+    parent: Option<&str>,
+) -> Result<()> {
+    if user.role == Role::Admin {
+        return Ok(());
+    }
+    let page = store.get_page_info(page_id).ok_or(Error::PageNotFound)?;
+    if !page.access.is_empty() {
+        return Ok(());
+    }
+    let before = match &page.parent {
+        Some(parent) => closest_access(store, parent)?,
+        None => vec![],
+    };
+    let after = match parent {
+        Some(parent) => closest_access(store, parent)?,
+        None => vec![],
+    };
+    match before == after {
+        true => Ok(()),
+        false => Err(Error::Forbidden),
+    }
+}
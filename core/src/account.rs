@@ -0,0 +1,364 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{OnceLock, RwLock},
+};
+
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use chrono::{DateTime, Duration, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// How long a login token stays valid
+const SESSION_HOURS: i64 = 12;
+/// Failed logins of a name allowed before it is locked out for a while
+const FREE_ATTEMPTS: u32 = 5;
+/// First lock out of a name, doubled with every failure after it
+const LOCK_SECONDS: i64 = 30;
+const MAX_LOCK_SECONDS: i64 = 3600;
+
+/// What a user is allowed to do, every role can do what the previous ones can
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Edits and publishes the content of the site
+    Editor,
+    /// Changes the behavior and the look of the site
+    Developer,
+    /// Manages the users and their rights
+    Admin,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct User {
+    pub name: String,
+    pub role: Role,
+    /// Groups page access entries can refer to
+    pub groups: Vec<String>,
+}
+
+/// A logged in user and the token to send with each request
+#[derive(Debug, Clone, Serialize)]
+pub struct Session {
+    pub token: String,
+    pub user: User,
+    pub expires: DateTime<Utc>,
+}
+
+/// Changes to an account, the ones left out are kept
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UserChanges {
+    pub password: Option<String>,
+    pub role: Option<Role>,
+    pub groups: Option<Vec<String>>,
+}
+
+/// Logins failed since the last successful one of a name
+#[derive(Debug, Clone, Copy)]
+struct FailedLogins {
+    count: u32,
+    last: DateTime<Utc>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UserEntry {
+    password: String,
+    role: Role,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<String>,
+}
+
+/// Local user accounts saved in a yaml file, and their sessions kept in
+/// memory
+#[derive(Debug)]
+pub struct Accounts {
+    file: PathBuf,
+    users: RwLock<HashMap<String, UserEntry>>,
+    sessions: RwLock<HashMap<String, Session>>,
+    failures: RwLock<HashMap<String, FailedLogins>>,
+}
+
+impl Accounts {
+    /// Load the accounts of the file, a missing file has no account yet
+    pub fn load(path: &str) -> Result<Self> {
+        let file = PathBuf::from(path);
+        let users = match fs::File::open(&file) {
+            Ok(reader) => serde_yaml::from_reader(reader)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Self {
+            file,
+            users: RwLock::new(users),
+            sessions: RwLock::new(HashMap::new()),
+            failures: RwLock::new(HashMap::new()),
+        })
+    }
+
+    fn persist(&self, users: &HashMap<String, UserEntry>) -> Result<()> {
+        if let Some(folder) = self.file.parent().filter(|f| !f.as_os_str().is_empty()) {
+            fs::create_dir_all(folder)?;
+        }
+        let writer = fs::File::create(&self.file)?;
+        serde_yaml::to_writer(writer, users)?;
+        Ok(())
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.read().unwrap().is_empty()
+    }
+
+    /// Every user sorted by name
+    pub fn users(&self) -> Vec<User> {
+        let users_lock = self.users.read().unwrap();
+        let mut users: Vec<User> = users_lock
+            .iter()
+            .map(|(name, entry)| to_user(name, entry))
+            .collect();
+        users.sort_by(|a, b| a.name.cmp(&b.name));
+        users
+    }
+
+    pub fn get_user(&self, name: &str) -> Option<User> {
+        let users_lock = self.users.read().unwrap();
+        users_lock.get(name).map(|entry| to_user(name, entry))
+    }
+
+    pub fn create_user(&self, name: &str, password: &str, role: Role) -> Result<User> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(Error::EmptyUserName);
+        }
+        let password = hash_password(password)?;
+        let mut users_lock = self.users.write().unwrap();
+        if users_lock.contains_key(name) {
+            return Err(Error::DuplicatedUser);
+        }
+        let entry = UserEntry {
+            password,
+            role,
+            groups: vec![],
+        };
+        let user = to_user(name, &entry);
+        users_lock.insert(name.to_owned(), entry);
+        self.persist(&users_lock)?;
+        Ok(user)
+    }
+
+    /// Apply every change to an account at once, nothing is changed when
+    /// one of them is refused. The site always keeps an admin, and a new
+    /// password ends the sessions of the user.
+    pub fn update_user(&self, name: &str, changes: UserChanges) -> Result<User> {
+        let password = changes.password.as_deref().map(hash_password).transpose()?;
+        let mut users_lock = self.users.write().unwrap();
+        let entry = users_lock.get(name).ok_or(Error::UserNotFound)?;
+        let demoted = matches!(changes.role, Some(role) if role != Role::Admin);
+        if entry.role == Role::Admin && demoted && count_admins(&users_lock) == 1 {
+            return Err(Error::LastAdmin);
+        }
+
+        let entry = users_lock.get_mut(name).ok_or(Error::UserNotFound)?;
+        if let Some(role) = changes.role {
+            entry.role = role;
+        }
+        if let Some(groups) = changes.groups {
+            entry.groups = groups;
+        }
+        if let Some(password) = &password {
+            entry.password = password.to_owned();
+        }
+        let user = to_user(name, entry);
+        self.persist(&users_lock)?;
+        match password {
+            Some(..) => self.update_sessions(name, None),
+            None => self.update_sessions(name, Some(&user)),
+        }
+        Ok(user)
+    }
+
+    /// Remove a user and end its sessions, the last admin cannot be removed
+    pub fn delete_user(&self, name: &str) -> Result<User> {
+        let mut users_lock = self.users.write().unwrap();
+        let entry = users_lock.get(name).ok_or(Error::UserNotFound)?;
+        if entry.role == Role::Admin && count_admins(&users_lock) == 1 {
+            return Err(Error::LastAdmin);
+        }
+        let entry = users_lock.remove(name).ok_or(Error::UserNotFound)?;
+        self.persist(&users_lock)?;
+        self.update_sessions(name, None);
+        Ok(to_user(name, &entry))
+    }
+
+    /// Check the password of a user and open a session for it. After a few
+    /// failures the name is locked out for a time doubling with each new one.
+    pub fn login(&self, name: &str, password: &str) -> Result<Session> {
+        let now = Utc::now();
+        let locked_until = self
+            .failures
+            .read()
+            .unwrap()
+            .get(name)
+            .and_then(|failed| failed.locked_until);
+        if locked_until.is_some_and(|until| until > now) {
+            return Err(Error::TooManyAttempts);
+        }
+
+        let users_lock = self.users.read().unwrap();
+        let entry = match users_lock.get(name) {
+            Some(entry) if verify_password(&entry.password, password) => entry,
+            Some(..) => {
+                self.fail_login(name, now);
+                return Err(Error::InvalidCredentials);
+            }
+            None => {
+                // Take as long as for a known name, not to tell which exist
+                verify_password(dummy_hash(), password);
+                self.fail_login(name, now);
+                return Err(Error::InvalidCredentials);
+            }
+        };
+        self.failures.write().unwrap().remove(name);
+
+        let session = Session {
+            token: random_string(40),
+            user: to_user(name, entry),
+            expires: Utc::now() + Duration::hours(SESSION_HOURS),
+        };
+        let mut sessions_lock = self.sessions.write().unwrap();
+        sessions_lock.retain(|_, session| session.expires > Utc::now());
+        sessions_lock.insert(session.token.to_owned(), session.clone());
+        Ok(session)
+    }
+
+    /// User owning a token, as long as its session did not expire
+    pub fn authenticate(&self, token: &str) -> Result<User> {
+        let sessions_lock = self.sessions.read().unwrap();
+        match sessions_lock.get(token) {
+            Some(session) if session.expires > Utc::now() => Ok(session.user.clone()),
+            _ => Err(Error::Unauthenticated),
+        }
+    }
+
+    pub fn logout(&self, token: &str) {
+        self.sessions.write().unwrap().remove(token);
+    }
+
+    /// Create the first admin when there is no account yet, returning its
+    /// password. Without a password one is generated and handed to `keep`
+    /// first, the account is not created when it cannot be kept.
+    pub fn ensure_admin(
+        &self,
+        name: &str,
+        password: Option<&str>,
+        keep: impl FnOnce(&str) -> Result<()>,
+    ) -> Result<Option<String>> {
+        if !self.is_empty() {
+            return Ok(None);
+        }
+        let password = match password {
+            Some(password) => password.to_owned(),
+            None => {
+                let password = random_string(20);
+                keep(&password)?;
+                password
+            }
+        };
+        self.create_user(name, &password, Role::Admin)?;
+        Ok(Some(password))
+    }
+
+    /// Count a failed login of a name, and lock it out once it had its free
+    /// attempts. Names with no failure for a day are forgotten.
+    fn fail_login(&self, name: &str, now: DateTime<Utc>) {
+        let mut failures_lock = self.failures.write().unwrap();
+        failures_lock.retain(|_, failed| failed.last > now - Duration::days(1));
+        let failed = failures_lock
+            .entry(name.to_owned())
+            .or_insert(FailedLogins {
+                count: 0,
+                last: now,
+                locked_until: None,
+            });
+        failed.count += 1;
+        failed.last = now;
+        if failed.count >= FREE_ATTEMPTS {
+            let doublings = (failed.count - FREE_ATTEMPTS).min(16);
+            let seconds = (LOCK_SECONDS << doublings).min(MAX_LOCK_SECONDS);
+            failed.locked_until = Some(now + Duration::seconds(seconds));
+        }
+    }
+
+    /// Follow a change of user in its sessions, or end them when `user` is
+    /// `None`
+    fn update_sessions(&self, name: &str, user: Option<&User>) {
+        let mut sessions_lock = self.sessions.write().unwrap();
+        match user {
+            Some(user) => sessions_lock
+                .values_mut()
+                .filter(|session| session.user.name == name)
+                .for_each(|session| session.user = user.clone()),
+            None => sessions_lock.retain(|_, session| session.user.name != name),
+        }
+    }
+}
+
+fn to_user(name: &str, entry: &UserEntry) -> User {
+    User {
+        name: name.to_owned(),
+        role: entry.role,
+        groups: entry.groups.to_owned(),
+    }
+}
+
+fn count_admins(users: &HashMap<String, UserEntry>) -> usize {
+    users
+        .values()
+        .filter(|entry| entry.role == Role::Admin)
+        .count()
+}
+
+fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    })
+}
+
+/// Hash checked against when a name has no account, made once from a
+/// random password
+fn dummy_hash() -> &'static str {
+    static HASH: OnceLock<String> = OnceLock::new();
+    HASH.get_or_init(|| hash_password(&random_string(20)).unwrap_or_default())
+}
+
+fn hash_password(password: &str) -> Result<String> {
+    if password.is_empty() {
+        return Err(Error::EmptyPassword);
+    }
+    let salt: [u8; 16] = rand::thread_rng().gen();
+    let salt = SaltString::encode_b64(&salt).map_err(|_| Error::InvalidValue)?;
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|_| Error::InvalidValue)?;
+    Ok(hash.to_string())
+}
+
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
@@ -0,0 +1,115 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{account::User, diff::Change};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    PageCreated,
+    /// Fields or modules of the draft changed
+    PageEdited,
+    SlugChanged,
+    PageMoved,
+    PagePublished,
+    PageUnpublished,
+    PageScheduled,
+    PageDeleted,
+    RevisionRestored,
+    AccessChanged,
+    UserCreated,
+    UserUpdated,
+    UserDeleted,
+    MediaUploaded,
+    MediaUpdated,
+    MediaDeleted,
+}
+
+/// One change made to the site, the log only ever grows
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub date: DateTime<Utc>,
+    /// Who made the change, `None` for the scheduler
+    pub user: Option<String>,
+    pub action: AuditAction,
+    /// Id of the page changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<String>,
+    /// Name of the account changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    /// Id of the media changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+    /// Content changes of an edit
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<Change>,
+}
+
+impl AuditEntry {
+    pub fn new(user: Option<&User>, action: AuditAction) -> Self {
+        Self {
+            date: Utc::now(),
+            user: user.map(|user| user.name.to_owned()),
+            action,
+            page: None,
+            account: None,
+            media: None,
+            before: None,
+            after: None,
+            changes: vec![],
+        }
+    }
+
+    pub fn page(mut self, id: &str) -> Self {
+        self.page = Some(id.to_owned());
+        self
+    }
+
+    pub fn account(mut self, name: &str) -> Self {
+        self.account = Some(name.to_owned());
+        self
+    }
+
+    pub fn media(mut self, id: &str) -> Self {
+        self.media = Some(id.to_owned());
+        self
+    }
+
+    /// Short description of what changed, either side can be missing
+    pub fn summary(mut self, before: Option<String>, after: Option<String>) -> Self {
+        self.before = before;
+        self.after = after;
+        self
+    }
+
+    pub fn changes(mut self, changes: Vec<Change>) -> Self {
+        self.changes = changes;
+        self
+    }
+}
+
+/// Criteria an entry must all meet to be listed, the date range includes
+/// both ends
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AuditFilter {
+    pub user: Option<String>,
+    pub page: Option<String>,
+    pub action: Option<AuditAction>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        (self.user.is_none() || self.user == entry.user)
+            && (self.page.is_none() || self.page == entry.page)
+            && self.action.is_none_or(|action| action == entry.action)
+            && self.from.is_none_or(|from| entry.date >= from)
+            && self.to.is_none_or(|to| entry.date <= to)
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::{Error, Result},
+    module::Module,
+    renderer::Render,
+    store::Page,
+    theme::Theme,
+    INDEX_SLUG,
+};
+
+/// File kept in the output folder to know what a previous build produced
+pub(crate) const MANIFEST_FILE: &str = ".jilo-manifest.yml";
+
+/// Outcome of a static build, a failing page never stops the others
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    /// Pages rendered and written during this build
+    pub pages: Vec<BuiltPage>,
+    /// Pages whose content and templates did not change since the last build
+    pub skipped: Vec<BuiltPage>,
+    /// Output folders of pages that are no longer in the store or moved
+    pub removed: Vec<PathBuf>,
+    pub failures: Vec<BuildFailure>,
+}
+
+impl BuildReport {
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+#[derive(Debug)]
+pub struct BuiltPage {
+    pub id: String,
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct BuildFailure {
+    pub id: String,
+    pub name: String,
+    pub error: Error,
+}
+
+/// Html files of the last build, as long as they match what the site
+/// serves. Any change to the online pages or to the theme outdates them all.
+#[derive(Debug, Default)]
+pub(crate) struct BuiltFiles {
+    /// Bumped on every change, the files of a build started before are not
+    /// kept
+    generation: u64,
+    files: HashMap<String, PathBuf>,
+}
+
+impl BuiltFiles {
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub(crate) fn outdate(&mut self) {
+        self.generation += 1;
+        self.files.clear();
+    }
+
+    /// Keep the files of a build started at `generation`
+    pub(crate) fn update(&mut self, generation: u64, report: &BuildReport) {
+        if generation != self.generation {
+            return;
+        }
+        self.files = report
+            .pages
+            .iter()
+            .chain(&report.skipped)
+            .map(|page| (page.id.to_owned(), page.path.to_owned()))
+            .collect();
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<PathBuf> {
+        self.files.get(id).cloned()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    pages: HashMap<String, ManifestEntry>,
+}
+
+/// Hashes of every input used to render a page
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct ManifestEntry {
+    path: String,
+    module: String,
+    templates: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// A missing or unreadable manifest only means everything is rebuilt
+    fn load(output_dir: &Path) -> Self {
+        fs::read(output_dir.join(MANIFEST_FILE))
+            .ok()
+            .and_then(|content| serde_yaml::from_slice(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, output_dir: &Path) -> Result<()> {
+        let file = fs::File::create(output_dir.join(MANIFEST_FILE))?;
+        serde_yaml::to_writer(file, self)?;
+        Ok(())
+    }
+}
+
+/// Render the pages into `output_dir`, skipping the ones left untouched
+/// since the previous build and removing the ones deleted from the store.
+/// Pages built into the same folder, or into one of the `reserved` folders
+/// the build writes besides them, fail without stopping the others.
+pub(crate) fn build_pages(
+    theme: &Theme,
+    renderer: &dyn Render,
+    pages: Vec<Page>,
+    mut modules: HashMap<String, Module>,
+    output_dir: &Path,
+    reserved: &[&str],
+) -> Result<BuildReport> {
+    let mut collisions = collisions(&pages, reserved);
+    fs::create_dir_all(output_dir)?;
+    let mut previous = Manifest::load(output_dir);
+    let mut manifest = Manifest::default();
+    let mut view_hashes = HashMap::new();
+    let mut report = BuildReport::default();
+
+    for page in &pages {
+        let folder = output_folder(&page.path);
+        let path = output_dir.join(folder).join("index.html");
+
+        let built = match collisions.remove(&page.id) {
+            Some(error) => Err(error),
+            None => modules.remove(&page.id).ok_or(Error::PageNotFound),
+        }
+        .and_then(|module| {
+            let entry = ManifestEntry {
+                path: folder.to_owned(),
+                module: hash_module(&module),
+                templates: hash_templates(theme, &module, &mut view_hashes)?,
+            };
+            let unchanged = previous.pages.get(&page.id) == Some(&entry) && path.is_file();
+            if !unchanged {
+                let html = renderer.render_module(&module)?;
+                write_page(output_dir, folder, &html)?;
+            }
+            manifest.pages.insert(page.id.to_owned(), entry);
+            Ok(unchanged)
+        });
+
+        let built_page = BuiltPage {
+            id: page.id.to_owned(),
+            name: page.name.to_owned(),
+            path,
+        };
+        match built {
+            Ok(true) => report.skipped.push(built_page),
+            Ok(false) => report.pages.push(built_page),
+            Err(error) => {
+                // Keep the previous entry so its output is not seen as stale
+                if let Some(entry) = previous.pages.remove(&page.id) {
+                    manifest.pages.insert(page.id.to_owned(), entry);
+                }
+                report.failures.push(BuildFailure {
+                    id: page.id.to_owned(),
+                    name: page.name.to_owned(),
+                    error,
+                });
+            }
+        }
+    }
+
+    // Deepest pages first so their parents folders can be emptied after them
+    let mut stale: Vec<String> = previous
+        .pages
+        .into_values()
+        .map(|entry| entry.path)
+        .filter(|path| !manifest.pages.values().any(|e| &e.path == path))
+        .collect();
+    stale.sort_by_key(|path| std::cmp::Reverse(path.matches('/').count()));
+    for path in stale {
+        if let Some(removed) = remove_page(output_dir, &path)? {
+            report.removed.push(removed);
+        }
+    }
+
+    manifest.save(output_dir)?;
+    Ok(report)
+}
+
+/// Error of every page sharing its output folder with another page or
+/// writing into a reserved folder, keyed by page id. Building them would
+/// silently overwrite one with the other.
+fn collisions(pages: &[Page], reserved: &[&str]) -> HashMap<String, Error> {
+    let mut paths: BTreeMap<&str, Vec<&Page>> = BTreeMap::new();
+    for page in pages {
+        paths.entry(page.path.as_str()).or_default().push(page);
+    }
+
+    let mut errors = HashMap::new();
+    for (path, pages) in paths {
+        let taken = reserved
+            .iter()
+            .find(|folder| path == **folder || path.starts_with(&format!("{}/", folder)));
+        if pages.len() < 2 && taken.is_none() {
+            continue;
+        }
+        let mut names: Vec<String> = pages.iter().map(|page| page.name.to_owned()).collect();
+        names.sort();
+        for page in pages {
+            let error = Error::OutputCollision {
+                path: taken.map_or(path, |folder| folder).to_owned(),
+                pages: names.to_owned(),
+            };
+            errors.insert(page.id.to_owned(), error);
+        }
+    }
+    errors
+}
+
+/// Folder of a page inside the output folder, the page served at the root
+/// of the site is written at the root of the output
+fn output_folder(path: &str) -> &str {
+    match path {
+        INDEX_SLUG => "",
+        path => path,
+    }
+}
+
+/// Write the html of a page as `<path>/index.html` inside the output folder
+fn write_page(output_dir: &Path, path: &str, html: &str) -> Result<PathBuf> {
+    let folder = output_dir.join(path);
+    fs::create_dir_all(&folder)?;
+    let path = folder.join("index.html");
+    fs::write(&path, html)?;
+    Ok(path)
+}
+
+/// Remove the html of a page, then its folders left empty, without touching
+/// the pages nested under it
+fn remove_page(output_dir: &Path, path: &str) -> Result<Option<PathBuf>> {
+    let page_folder = output_dir.join(path);
+    let file = page_folder.join("index.html");
+    if !file.is_file() {
+        return Ok(None);
+    }
+    fs::remove_file(&file)?;
+
+    let mut folder = file.parent();
+    while let Some(current) = folder.filter(|f| *f != output_dir) {
+        if fs::remove_dir(current).is_err() {
+            break;
+        }
+        folder = current.parent();
+    }
+    Ok(Some(page_folder))
+}
+
+/// Hash a module tree independently of the order of its hash maps
+fn hash_module(module: &Module) -> String {
+    let mut hasher = Sha256::new();
+    feed_module(&mut hasher, module);
+    to_hex(&hasher.finalize())
+}
+
+fn feed_module(hasher: &mut Sha256, module: &Module) {
+    hasher.update(module.template.as_bytes());
+
+    let fields: BTreeMap<_, _> = module.fields.iter().collect();
+    for (name, value) in fields {
+        hasher.update(name.as_bytes());
+        hasher.update(serde_yaml::to_string(value).unwrap_or_default().as_bytes());
+    }
+
+    let areas: BTreeMap<_, _> = module.areas.iter().collect();
+    for (name, modules) in areas {
+        hasher.update(name.as_bytes());
+        hasher.update(modules.len().to_le_bytes());
+        for module in modules {
+            feed_module(hasher, module);
+        }
+    }
+}
+
+/// Hash the view of every template used by the module and its areas, as
+/// the renderer compiled it
+fn hash_templates(
+    theme: &Theme,
+    module: &Module,
+    cache: &mut HashMap<String, String>,
+) -> Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    for name in module.templates() {
+        if let Some(hash) = cache.get(name) {
+            hashes.insert(name.to_owned(), hash.to_owned());
+            continue;
+        }
+        let template = theme.templates.get(name).ok_or(Error::TemplateNotFound)?;
+        let hash = to_hex(&Sha256::digest(template.source.as_bytes()));
+        cache.insert(name.to_owned(), hash.to_owned());
+        hashes.insert(name.to_owned(), hash);
+    }
+
+    Ok(hashes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    mod hash {
+        use crate::{build::hash_module, module::Module};
+        use serde_yaml::Value;
+
+        #[test]
+        fn hash_ignores_map_order() {
+            let mut first = Module::new("/pages/section");
+            first.fields.insert("a".into(), Value::from("1"));
+            first.fields.insert("b".into(), Value::from("2"));
+            let mut second = Module::new("/pages/section");
+            second.fields.insert("b".into(), Value::from("2"));
+            second.fields.insert("a".into(), Value::from("1"));
+
+            assert_eq!(hash_module(&first), hash_module(&second));
+
+            second.fields.insert("a".into(), Value::from("3"));
+            assert_ne!(hash_module(&first), hash_module(&second));
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::module::Module;
+
+/// A difference between two versions of a page. Paths are module paths,
+/// `area_1/0/inner_area/2`, the page itself being the empty path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    /// The module uses another template, its content is not compared
+    TemplateChanged {
+        path: String,
+        from: String,
+        to: String,
+    },
+    /// A field was set, changed or removed
+    FieldChanged {
+        path: String,
+        field: String,
+        from: Option<Value>,
+        to: Option<Value>,
+    },
+    ModuleAdded {
+        path: String,
+        template: String,
+    },
+    ModuleRemoved {
+        path: String,
+        template: String,
+    },
+    /// The same module is now somewhere else, possibly in another area
+    ModuleMoved {
+        from: String,
+        to: String,
+        template: String,
+    },
+}
+
+/// Structural changes needed to go from `old` to `new`
+pub(crate) fn diff(old: &Module, new: &Module) -> Vec<Change> {
+    let mut diff = Diff::default();
+    diff.module("", "", old, new);
+    diff.finish()
+}
+
+/// Changes found so far, added and removed modules are kept apart until the
+/// end to recognise the ones moved between areas
+#[derive(Default)]
+struct Diff<'a> {
+    changes: Vec<Change>,
+    added: Vec<(String, &'a Module)>,
+    removed: Vec<(String, &'a Module)>,
+}
+
+impl<'a> Diff<'a> {
+    fn module(&mut self, old_path: &str, path: &str, old: &'a Module, new: &'a Module) {
+        if old.template != new.template {
+            self.changes.push(Change::TemplateChanged {
+                path: path.to_string(),
+                from: old.template.to_owned(),
+                to: new.template.to_owned(),
+            });
+            return;
+        }
+
+        let fields: BTreeSet<&String> = old.fields.keys().chain(new.fields.keys()).collect();
+        for field in fields {
+            let (from, to) = (old.fields.get(field), new.fields.get(field));
+            if from != to {
+                self.changes.push(Change::FieldChanged {
+                    path: path.to_string(),
+                    field: field.to_owned(),
+                    from: from.cloned(),
+                    to: to.cloned(),
+                });
+            }
+        }
+
+        let areas: BTreeSet<&String> = old.areas.keys().chain(new.areas.keys()).collect();
+        for area in areas {
+            self.area(
+                &child_path(old_path, area),
+                &child_path(path, area),
+                old.areas.get(area).map_or(&[], Vec::as_slice),
+                new.areas.get(area).map_or(&[], Vec::as_slice),
+            );
+        }
+    }
+
+    fn area(&mut self, old_area: &str, area: &str, old: &'a [Module], new: &'a [Module]) {
+        // Pair identical modules, the first unpaired one wins
+        let mut old_pairs = vec![None; old.len()];
+        let mut pairs = vec![];
+        for (j, module) in new.iter().enumerate() {
+            let found = (0..old.len()).find(|i| old_pairs[*i].is_none() && &old[*i] == module);
+            if let Some(i) = found {
+                old_pairs[i] = Some(j);
+                pairs.push((i, j));
+            }
+        }
+
+        // Identical modules keeping their relative order did not move
+        let indexes: Vec<usize> = pairs.iter().map(|(i, _)| *i).collect();
+        let kept = longest_increasing(&indexes);
+        for (k, (i, j)) in pairs.iter().enumerate() {
+            if !kept.contains(&k) {
+                self.changes.push(Change::ModuleMoved {
+                    from: format!("{}/{}", old_area, i),
+                    to: format!("{}/{}", area, j),
+                    template: new[*j].template.to_owned(),
+                });
+            }
+        }
+
+        // A module changed in place is paired with the next unpaired module
+        // using the same template
+        let mut unpaired: Vec<usize> = (0..old.len()).filter(|i| old_pairs[*i].is_none()).collect();
+        let paired_new: BTreeSet<usize> = pairs.iter().map(|(_, j)| *j).collect();
+        for j in (0..new.len()).filter(|j| !paired_new.contains(j)) {
+            let path = format!("{}/{}", area, j);
+            match unpaired
+                .iter()
+                .position(|i| old[*i].template == new[j].template)
+            {
+                Some(position) => {
+                    let i = unpaired.remove(position);
+                    let old_path = format!("{}/{}", old_area, i);
+                    self.module(&old_path, &path, &old[i], &new[j]);
+                }
+                None => self.added.push((path, &new[j])),
+            }
+        }
+        for i in unpaired {
+            self.removed.push((format!("{}/{}", old_area, i), &old[i]));
+        }
+    }
+
+    fn finish(mut self) -> Vec<Change> {
+        for (path, module) in self.added {
+            let found = self
+                .removed
+                .iter()
+                .position(|(_, removed)| *removed == module);
+            match found {
+                Some(position) => {
+                    let (from, _) = self.removed.remove(position);
+                    self.changes.push(Change::ModuleMoved {
+                        from,
+                        to: path,
+                        template: module.template.to_owned(),
+                    });
+                }
+                None => self.changes.push(Change::ModuleAdded {
+                    path,
+                    template: module.template.to_owned(),
+                }),
+            }
+        }
+        for (path, module) in self.removed {
+            self.changes.push(Change::ModuleRemoved {
+                path,
+                template: module.template.to_owned(),
+            });
+        }
+        self.changes
+    }
+}
+
+fn child_path(path: &str, area: &str) -> String {
+    match path {
+        "" => area.to_string(),
+        path => format!("{}/{}", path, area),
+    }
+}
+
+/// Positions of one of the longest increasing subsequences of `values`
+fn longest_increasing(values: &[usize]) -> Vec<usize> {
+    let mut lengths = vec![1; values.len()];
+    let mut previous = vec![None; values.len()];
+    for k in 0..values.len() {
+        for l in 0..k {
+            if values[l] < values[k] && lengths[l] + 1 > lengths[k] {
+                lengths[k] = lengths[l] + 1;
+                previous[k] = Some(l);
+            }
+        }
+    }
+
+    let mut positions = vec![];
+    let mut current = (0..values.len()).max_by_key(|k| lengths[*k]);
+    while let Some(k) = current {
+        positions.push(k);
+        current = previous[k];
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    mod diff {
+        use crate::{
+            diff::{diff, Change},
+            module::Module,
+        };
+        use serde_yaml::Value;
+
+        fn module(template: &str, title: &str) -> Module {
+            let mut module = Module::new(template);
+            module.fields.insert("title".into(), Value::from(title));
+            module
+        }
+
+        #[test]
+        fn field_changes() {
+            let old = module("/pages/article", "Hello");
+            let mut new = module("/pages/article", "Bye");
+            new.fields.insert("subtitle".into(), Value::from("new"));
+
+            assert_eq!(
+                diff(&old, &new),
+                vec![
+                    Change::FieldChanged {
+                        path: "".into(),
+                        field: "subtitle".into(),
+                        from: None,
+                        to: Some(Value::from("new")),
+                    },
+                    Change::FieldChanged {
+                        path: "".into(),
+                        field: "title".into(),
+                        from: Some(Value::from("Hello")),
+                        to: Some(Value::from("Bye")),
+                    },
+                ]
+            );
+            assert!(diff(&old, &old).is_empty());
+        }
+
+        #[test]
+        fn module_changes() {
+            let mut old = Module::new("/pages/section");
+            old.areas.insert(
+                "main".into(),
+                vec![
+                    module("header", "a"),
+                    module("image", "b"),
+                    module("image", "c"),
+                ],
+            );
+            let mut new = Module::new("/pages/section");
+            new.areas.insert(
+                "main".into(),
+                vec![
+                    module("image", "c"),
+                    module("header", "a"),
+                    module("section", "d"),
+                ],
+            );
+            new.areas.insert("side".into(), vec![module("image", "b")]);
+
+            assert_eq!(
+                diff(&old, &new),
+                vec![
+                    Change::ModuleMoved {
+                        from: "main/2".into(),
+                        to: "main/0".into(),
+                        template: "image".into(),
+                    },
+                    Change::ModuleAdded {
+                        path: "main/2".into(),
+                        template: "section".into(),
+                    },
+                    Change::ModuleMoved {
+                        from: "main/1".into(),
+                        to: "side/0".into(),
+                        template: "image".into(),
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn nested_changes() {
+            let mut old = Module::new("/pages/section");
+            old.areas
+                .insert("main".into(), vec![module("section", "a")]);
+            let mut new = Module::new("/pages/section");
+            let mut section = module("section", "a");
+            section
+                .areas
+                .insert("content".into(), vec![module("image", "b")]);
+            new.areas.insert("main".into(), vec![section]);
+
+            assert_eq!(
+                diff(&new, &old),
+                vec![Change::ModuleRemoved {
+                    path: "main/0/content/0".into(),
+                    template: "image".into(),
+                }]
+            );
+        }
+    }
+}
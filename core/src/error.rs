@@ -1,5 +1,9 @@
 use std::{error::Error as StdError, fmt};
 
+use serde::Serialize;
+
+use crate::{reference::Reference, theme::Diagnostic};
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
@@ -10,10 +14,106 @@ pub enum Error {
     Io(std::io::Error),
     Tera(tera::Error),
     ParseTheme,
+    InvalidTheme(Vec<Diagnostic>),
     PageNotFound,
     TemplateNotFound,
     DuplicatedName,
+    DuplicatedSlug,
+    InvalidSlug,
     EmptyPageName,
+    PageCycle,
+    PageHasChildren,
+    PageNotPublished,
+    RevisionNotFound,
+    InvalidSchedule,
+    Validation(Vec<FieldError>),
+    InvalidModulePath,
+    ModuleNotFound,
+    AreaNotFound,
+    ComponentNotAccepted { area: String, template: String },
+    EmptyUserName,
+    EmptyPassword,
+    DuplicatedUser,
+    UserNotFound,
+    LastAdmin,
+    InvalidCredentials,
+    TooManyAttempts,
+    Unauthenticated,
+    Forbidden,
+    EmptyFile,
+    UnsupportedMedia,
+    MediaNotFound,
+    MediaInUse(Vec<Reference>),
+    NoMediaLibrary,
+    OutputCollision { path: String, pages: Vec<String> },
+}
+
+/// A submitted field value that does not match its template declaration
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub(crate) fn new(field: &str, message: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Error {
+    /// Stable name of the error, for clients to tell errors apart
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::EmptySummary => "empty_summary",
+            Error::InvalidValue => "invalid_value",
+            Error::Serde(..) => "serde",
+            Error::Io(..) => "io",
+            Error::Tera(..) => "tera",
+            Error::ParseTheme => "parse_theme",
+            Error::InvalidTheme(..) => "invalid_theme",
+            Error::PageNotFound => "page_not_found",
+            Error::TemplateNotFound => "template_not_found",
+            Error::DuplicatedName => "duplicated_name",
+            Error::DuplicatedSlug => "duplicated_slug",
+            Error::InvalidSlug => "invalid_slug",
+            Error::EmptyPageName => "empty_page_name",
+            Error::PageCycle => "page_cycle",
+            Error::PageHasChildren => "page_has_children",
+            Error::PageNotPublished => "page_not_published",
+            Error::RevisionNotFound => "revision_not_found",
+            Error::InvalidSchedule => "invalid_schedule",
+            Error::Validation(..) => "validation",
+            Error::InvalidModulePath => "invalid_module_path",
+            Error::ModuleNotFound => "module_not_found",
+            Error::AreaNotFound => "area_not_found",
+            Error::ComponentNotAccepted { .. } => "component_not_accepted",
+            Error::EmptyUserName => "empty_user_name",
+            Error::EmptyPassword => "empty_password",
+            Error::DuplicatedUser => "duplicated_user",
+            Error::UserNotFound => "user_not_found",
+            Error::LastAdmin => "last_admin",
+            Error::InvalidCredentials => "invalid_credentials",
+            Error::TooManyAttempts => "too_many_attempts",
+            Error::Unauthenticated => "unauthenticated",
+            Error::Forbidden => "forbidden",
+            Error::EmptyFile => "empty_file",
+            Error::UnsupportedMedia => "unsupported_media",
+            Error::MediaNotFound => "media_not_found",
+            Error::MediaInUse(..) => "media_in_use",
+            Error::NoMediaLibrary => "no_media_library",
+            Error::OutputCollision { .. } => "output_collision",
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
 }
 
 impl fmt::Display for Error {
@@ -25,10 +125,74 @@ impl fmt::Display for Error {
             Error::Serde(ref error) => write!(f, "serde error: {}", error),
             Error::Tera(..) => write!(f, "a tera error occured"),
             Error::ParseTheme => write!(f, "theme is not able to be parsed"),
+            Error::InvalidTheme(ref diagnostics) => {
+                write!(f, "theme has {} problem(s)", diagnostics.len())?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n{}", diagnostic)?;
+                }
+                Ok(())
+            }
             Error::PageNotFound => write!(f, "page is not in store"),
             Error::TemplateNotFound => write!(f, "template not found in theme"),
             Error::DuplicatedName => write!(f, "name already exist"),
+            Error::DuplicatedSlug => write!(f, "slug already exist"),
+            Error::InvalidSlug => write!(
+                f,
+                "slug must only have lowercase letters, digits and dashes"
+            ),
             Error::EmptyPageName => write!(f, "page name must be set"),
+            Error::PageCycle => write!(f, "a page cannot be moved under itself"),
+            Error::PageHasChildren => write!(f, "page still has children"),
+            Error::PageNotPublished => write!(f, "page is not published"),
+            Error::RevisionNotFound => write!(f, "revision not found"),
+            Error::InvalidSchedule => {
+                write!(f, "a page must be published before it is unpublished")
+            }
+            Error::InvalidModulePath => write!(f, "module path is malformed"),
+            Error::ModuleNotFound => write!(f, "no module at this path"),
+            Error::AreaNotFound => write!(f, "area is not declared by the template"),
+            Error::ComponentNotAccepted {
+                ref area,
+                ref template,
+            } => write!(f, "area {} does not accept component {}", area, template),
+            Error::Validation(ref errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "invalid fields: {}", errors.join(", "))
+            }
+            Error::EmptyUserName => write!(f, "user name must be set"),
+            Error::EmptyPassword => write!(f, "password must be set"),
+            Error::DuplicatedUser => write!(f, "user already exist"),
+            Error::UserNotFound => write!(f, "user not found"),
+            Error::LastAdmin => write!(f, "the last admin cannot be removed"),
+            Error::InvalidCredentials => write!(f, "wrong user name or password"),
+            Error::TooManyAttempts => write!(f, "too many failed logins, try again later"),
+            Error::Unauthenticated => write!(f, "authentication required"),
+            Error::Forbidden => write!(f, "not allowed for this role"),
+            Error::EmptyFile => write!(f, "file is empty"),
+            Error::UnsupportedMedia => write!(
+                f,
+                "only images, pdf, office documents and plain text can be uploaded"
+            ),
+            Error::MediaNotFound => write!(f, "media not found"),
+            Error::MediaInUse(ref references) => {
+                write!(f, "media is still used by {} field(s)", references.len())
+            }
+            Error::NoMediaLibrary => write!(f, "no media library is configured"),
+            Error::OutputCollision {
+                ref path,
+                ref pages,
+            } if pages.len() < 2 => {
+                write!(
+                    f,
+                    "page {} is built into the reserved {}",
+                    pages.join(""),
+                    path
+                )
+            }
+            Error::OutputCollision {
+                ref path,
+                ref pages,
+            } => write!(f, "pages {} are all built into {}", pages.join(", "), path),
         }
     }
 }
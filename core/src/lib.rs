@@ -1,103 +1,1090 @@
+mod access;
+mod account;
+mod audit;
+mod build;
+mod diff;
 mod error;
+mod media;
 mod module;
+mod reference;
 mod renderer;
+mod schedule;
 mod store;
 mod theme;
+mod validation;
 
+use chrono::{DateTime, Utc};
 use std::{
-    path::PathBuf,
-    sync::{Arc, Mutex},
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
 };
 
-use module::Module;
-use serde::{Deserialize, Serialize};
-use store::Page;
+use module::{AreaPath, ModulePath};
+use serde::Serialize;
+pub use store::{Page, PageNode, PageStatus, Revision};
 
 use crate::{
+    access::{check, check_move, check_site, is_allowed},
+    build::{build_pages, BuiltFiles},
+    diff::diff,
     error::Result,
+    reference::{resolve_module, ReferenceIndex},
     renderer::{tera_renderer::TeraRenderer, Render},
-    store::{yaml_storage::YamlStorage, Store},
+    store::{
+        slug::{is_valid_slug, slugify},
+        yaml_storage::YamlStorage,
+        Store,
+    },
     theme::Theme,
+    validation::{validate_fields, validate_module},
 };
 
-pub use crate::error::Error;
+pub use crate::{
+    access::{AccessEntry, Right, Subject},
+    account::{Accounts, Role, Session, User, UserChanges},
+    audit::{AuditAction, AuditEntry, AuditFilter},
+    build::{BuildFailure, BuildReport, BuiltPage},
+    diff::Change,
+    error::{Error, FieldError},
+    media::{Media, MediaFilter, MediaLibrary},
+    module::Module,
+    reference::Reference,
+    schedule::{Clock, ScheduleAction, ScheduledChange, SystemClock},
+    theme::{
+        Area, AreaDefinition, Diagnostic, Field, FieldDefinition, PageRef, Pattern, Severity,
+        TemplateDefinition, TemplateKind,
+    },
+};
+pub use serde_yaml::Value;
+
+/// Slug of the page served at the root of the site
+const INDEX_SLUG: &str = "index";
+/// Folder of a static build receiving the files of the media library
+const MEDIA_FOLDER: &str = "media";
+/// Template rendered when no page matches an url
+const NOT_FOUND_TEMPLATE: &str = "/pages/not_found";
+const DEFAULT_NOT_FOUND: &str = "<!DOCTYPE html><html><body><h1>Page not found</h1></body></html>";
+
+/// Draft of a page with the definition of every template it uses, enough
+/// for an editor to show it
+#[derive(Debug, Serialize)]
+pub struct PageContent {
+    #[serde(flatten)]
+    pub page: Page,
+    pub module: Module,
+    /// Keyed by template name, templates missing from the theme are left out
+    pub templates: BTreeMap<String, TemplateDefinition>,
+}
 
 #[derive(Debug)]
 pub struct Site {
-    theme: Theme,
-    storage: Arc<Mutex<dyn Store>>,
-    renderer: Arc<Mutex<dyn Render>>,
+    theme: RwLock<Arc<Theme>>,
+    storage: Arc<RwLock<dyn Store>>,
+    renderer: Arc<RwLock<dyn Render>>,
+    clock: Arc<dyn Clock>,
+    media: Option<MediaLibrary>,
+    built: RwLock<BuiltFiles>,
+    references: RwLock<ReferenceIndex>,
 }
 
 impl Site {
+    /// Create a site and compile the templates of its theme, they are only
+    /// compiled again when the theme is reloaded
     pub fn new(
         theme: Theme,
-        storage: Arc<Mutex<dyn Store>>,
-        renderer: Arc<Mutex<dyn Render>>,
-    ) -> Self {
-        Self {
-            theme,
+        storage: Arc<RwLock<dyn Store>>,
+        renderer: Arc<RwLock<dyn Render>>,
+    ) -> Result<Self> {
+        renderer.write().unwrap().load(&theme)?;
+        Ok(Self {
+            theme: RwLock::new(Arc::new(theme)),
             storage,
             renderer,
-        }
+            clock: Arc::new(SystemClock),
+            media: None,
+            built: RwLock::new(BuiltFiles::default()),
+            references: RwLock::new(ReferenceIndex::default()),
+        })
+    }
+
+    /// Current theme, a reload never changes it under a running operation
+    fn theme(&self) -> Arc<Theme> {
+        Arc::clone(&self.theme.read().unwrap())
+    }
+
+    /// Parse the theme folder again and swap it with the renderer templates
+    /// in one go. On error the site keeps running with the previous theme.
+    pub fn reload_theme(&self) -> Result<()> {
+        let folder = self.theme().folder.to_owned();
+        let theme = Theme::from_folder(&folder)?;
+
+        let mut renderer_lock = self.renderer.write().unwrap();
+        renderer_lock.load(&theme)?;
+        *self.theme.write().unwrap() = Arc::new(theme);
+        self.references.write().unwrap().clear();
+        self.outdate_builds();
+        Ok(())
     }
 
-    pub fn create_page(&self, name: &str, template: &str) -> Result<String> {
+    /// Definition of every template of the theme pages can be created
+    /// with, keyed by name. The not found page is only rendered by the site.
+    pub fn templates(&self) -> Result<BTreeMap<String, TemplateDefinition>> {
+        let theme = self.theme();
+        theme
+            .templates
+            .keys()
+            .filter(|name| *name != NOT_FOUND_TEMPLATE)
+            .map(|name| Ok((name.to_owned(), theme.definition(name)?)))
+            .collect()
+    }
+
+    pub fn template(&self, name: &str) -> Result<TemplateDefinition> {
+        self.theme().definition(name)
+    }
+
+    /// Folder the theme is loaded from
+    pub fn theme_folder(&self) -> PathBuf {
+        self.theme().folder.to_owned()
+    }
+
+    pub fn create_page(&self, user: &User, name: &str, template: &str) -> Result<String> {
         let name = name.trim();
         if name.is_empty() {
             return Err(Error::EmptyPageName);
         }
-        let module = self.theme.get_module_defaults(template)?;
-        let mut storage_lock = self.storage.lock().unwrap();
+        let slug = slugify(name);
+        if !is_valid_slug(&slug) {
+            return Err(Error::InvalidSlug);
+        }
+        if template == NOT_FOUND_TEMPLATE {
+            return Err(Error::TemplateNotFound);
+        }
+        let module = self.theme().get_module_defaults(template)?;
+        let mut storage_lock = self.storage.write().unwrap();
         if storage_lock.get_page_by_name(name).is_some() {
             return Err(Error::DuplicatedName);
         }
-        storage_lock.create_page(name, module)
+        if storage_lock.get_page_by_slug(&slug).is_some() {
+            return Err(Error::DuplicatedSlug);
+        }
+        let id =
+            storage_lock.create_page(name, &slug, module, Some(&user.name), self.clock.now())?;
+        self.index_page(&*storage_lock, &id);
+        let entry = AuditEntry::new(Some(user), AuditAction::PageCreated)
+            .page(&id)
+            .summary(None, Some(name.to_owned()));
+        self.log(&mut *storage_lock, entry)?;
+        Ok(id)
     }
 
-    pub fn delete_page(&self, id: &str) -> Result<Module> {
-        let mut storage_lock = self.storage.lock().unwrap();
-        if !storage_lock.page_exists(id) {
-            return Err(Error::PageNotFound);
+    /// Change the slug of a page, it must be valid and not used by another page
+    pub fn update_slug(&self, user: &User, id: &str, slug: &str) -> Result<Page> {
+        let slug = slug.trim();
+        if !is_valid_slug(slug) {
+            return Err(Error::InvalidSlug);
+        }
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, id, Right::Edit)?;
+        match storage_lock.get_page_by_slug(slug) {
+            Some(page) if page.id != id => return Err(Error::DuplicatedSlug),
+            _ => {}
+        }
+        let before = storage_lock.get_page_info(id).map(|page| page.slug);
+        storage_lock.update_slug(id, slug)?;
+        self.outdate_builds();
+        let entry = AuditEntry::new(Some(user), AuditAction::SlugChanged)
+            .page(id)
+            .summary(before, Some(slug.to_owned()));
+        self.log(&mut *storage_lock, entry)?;
+        storage_lock
+            .get_page_by_slug(slug)
+            .ok_or(Error::PageNotFound)
+    }
+
+    /// A page the user can view
+    pub fn page(&self, user: &User, id: &str) -> Result<Page> {
+        let storage_lock = self.storage.read().unwrap();
+        check(&*storage_lock, user, id, Right::View)?;
+        storage_lock.get_page_info(id).ok_or(Error::PageNotFound)
+    }
+
+    /// A page the user can view with its whole module tree
+    pub fn page_content(&self, user: &User, id: &str) -> Result<PageContent> {
+        let storage_lock = self.storage.read().unwrap();
+        check(&*storage_lock, user, id, Right::View)?;
+        let page = storage_lock.get_page_info(id).ok_or(Error::PageNotFound)?;
+        let module = storage_lock.get_page(id).ok_or(Error::PageNotFound)?;
+        drop(storage_lock);
+
+        let theme = self.theme();
+        let templates = module
+            .templates()
+            .into_iter()
+            .filter_map(|name| {
+                let definition = theme.definition(name).ok()?;
+                Some((name.to_owned(), definition))
+            })
+            .collect();
+        Ok(PageContent {
+            page,
+            module,
+            templates,
+        })
+    }
+
+    /// Find a page from its slug
+    pub fn get_page_by_slug(&self, slug: &str) -> Option<Page> {
+        let storage_lock = self.storage.read().unwrap();
+        storage_lock.get_page_by_slug(slug)
+    }
+
+    /// Remove a page without children, the fields of other pages still
+    /// holding its id are returned for editors to fix them
+    pub fn delete_page(&self, user: &User, id: &str) -> Result<Vec<Reference>> {
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, id, Right::Delete)?;
+        let summary = storage_lock.summary();
+        if summary
+            .iter()
+            .any(|page| page.parent.as_deref() == Some(id))
+        {
+            return Err(Error::PageHasChildren);
+        }
+        let mut references = self.references_to(&*storage_lock, id, false)?;
+        references.retain(|reference| reference.page != id);
+
+        let before = storage_lock.get_page_info(id).map(|page| page.name);
+        storage_lock.delete_page(id)?;
+        self.index_page(&*storage_lock, id);
+        self.outdate_builds();
+        let entry = AuditEntry::new(Some(user), AuditAction::PageDeleted)
+            .page(id)
+            .summary(before, None);
+        self.log(&mut *storage_lock, entry)?;
+        Ok(references)
+    }
+
+    /// Put a page under another one, or at the root of the site when
+    /// `parent` is `None`, at `position` among its new siblings
+    pub fn move_page(
+        &self,
+        user: &User,
+        id: &str,
+        parent: Option<&str>,
+        position: usize,
+    ) -> Result<Page> {
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, id, Right::Edit)?;
+
+        let mut ancestor = parent.map(str::to_owned);
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == id {
+                return Err(Error::PageCycle);
+            }
+            ancestor = storage_lock
+                .get_page_info(&ancestor_id)
+                .ok_or(Error::PageNotFound)?
+                .parent;
+        }
+        if let Some(parent) = parent {
+            check(&*storage_lock, user, parent, Right::Edit)?;
+        }
+        check_move(&*storage_lock, user, id, parent)?;
+
+        let before = storage_lock.get_page_info(id).map(|page| page.path);
+        storage_lock.move_page(id, parent, position)?;
+        self.outdate_builds();
+        let page = storage_lock.get_page_info(id).ok_or(Error::PageNotFound)?;
+        let entry = AuditEntry::new(Some(user), AuditAction::PageMoved)
+            .page(id)
+            .summary(before, Some(page.path.to_owned()));
+        self.log(&mut *storage_lock, entry)?;
+        Ok(page)
+    }
+
+    /// Make the current draft of a page the version shown on the site. Every
+    /// module of the draft is checked first, required fields included.
+    pub fn publish_page(&self, user: &User, id: &str) -> Result<Page> {
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, id, Right::Publish)?;
+        let module = storage_lock.get_page(id).ok_or(Error::PageNotFound)?;
+        validate_module(
+            &*storage_lock,
+            self.media.as_ref(),
+            &self.theme(),
+            &module,
+            true,
+        )?;
+        storage_lock.publish_page(id)?;
+        self.index_page(&*storage_lock, id);
+        self.outdate_builds();
+        let entry = AuditEntry::new(Some(user), AuditAction::PagePublished).page(id);
+        self.log(&mut *storage_lock, entry)?;
+        storage_lock.get_page_info(id).ok_or(Error::PageNotFound)
+    }
+
+    /// Take a page off the site, its draft is kept
+    pub fn unpublish_page(&self, user: &User, id: &str) -> Result<Page> {
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, id, Right::Publish)?;
+        storage_lock.unpublish_page(id)?;
+        self.index_page(&*storage_lock, id);
+        self.outdate_builds();
+        let entry = AuditEntry::new(Some(user), AuditAction::PageUnpublished).page(id);
+        self.log(&mut *storage_lock, entry)?;
+        storage_lock.get_page_info(id).ok_or(Error::PageNotFound)
+    }
+
+    /// Set when a page goes live and when it comes down, either can be left
+    /// out. The page stays hidden outside of this window.
+    pub fn schedule_page(
+        &self,
+        user: &User,
+        id: &str,
+        publish_at: Option<DateTime<Utc>>,
+        unpublish_at: Option<DateTime<Utc>>,
+    ) -> Result<Page> {
+        if let (Some(publish_at), Some(unpublish_at)) = (publish_at, unpublish_at) {
+            if unpublish_at <= publish_at {
+                return Err(Error::InvalidSchedule);
+            }
+        }
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, id, Right::Publish)?;
+        let before = storage_lock
+            .get_page_info(id)
+            .map(|page| describe_window(page.publish_at, page.unpublish_at));
+        storage_lock.schedule_page(id, publish_at, unpublish_at)?;
+        self.outdate_builds();
+        let entry = AuditEntry::new(Some(user), AuditAction::PageScheduled)
+            .page(id)
+            .summary(before, Some(describe_window(publish_at, unpublish_at)));
+        self.log(&mut *storage_lock, entry)?;
+        storage_lock.get_page_info(id).ok_or(Error::PageNotFound)
+    }
+
+    /// Publish and unpublish the pages whose scheduled time has come, returns
+    /// the changes applied. A draft that cannot be published yet keeps its
+    /// publication pending until its fields are fixed.
+    pub fn apply_schedule(&self) -> Result<Vec<ScheduledChange>> {
+        let now = self.clock.now();
+        let theme = self.theme();
+        let mut storage_lock = self.storage.write().unwrap();
+        let mut applied = vec![];
+        for page in storage_lock.summary() {
+            let mut due: Vec<ScheduledChange> = ScheduledChange::of_page(&page)
+                .into_iter()
+                .filter(|change| change.at <= now)
+                .collect();
+            let mut publish_at = page.publish_at.filter(|at| *at > now);
+            if due
+                .iter()
+                .any(|change| change.action == ScheduleAction::Publish)
+            {
+                let module = storage_lock.get_page(&page.id).ok_or(Error::PageNotFound)?;
+                let media = self.media.as_ref();
+                if validate_module(&*storage_lock, media, &theme, &module, true).is_err() {
+                    due.retain(|change| change.action != ScheduleAction::Publish);
+                    publish_at = page.publish_at;
+                }
+            }
+            if due.is_empty() {
+                continue;
+            }
+            for change in &due {
+                let action = match change.action {
+                    ScheduleAction::Publish => {
+                        storage_lock.publish_page(&page.id)?;
+                        self.index_page(&*storage_lock, &page.id);
+                        AuditAction::PagePublished
+                    }
+                    ScheduleAction::Unpublish => {
+                        storage_lock.unpublish_page(&page.id)?;
+                        self.index_page(&*storage_lock, &page.id);
+                        AuditAction::PageUnpublished
+                    }
+                };
+                self.log(
+                    &mut *storage_lock,
+                    AuditEntry::new(None, action).page(&page.id),
+                )?;
+            }
+            let unpublish_at = page.unpublish_at.filter(|at| *at > now);
+            storage_lock.schedule_page(&page.id, publish_at, unpublish_at)?;
+            applied.extend(due);
+        }
+        if !applied.is_empty() {
+            self.outdate_builds();
+        }
+        applied.sort_by(|a, b| (a.at, &a.name).cmp(&(b.at, &b.name)));
+        Ok(applied)
+    }
+
+    /// Scheduled changes not applied yet of the pages the user can view,
+    /// soonest first then by page name
+    pub fn upcoming_changes(&self, user: &User) -> Vec<ScheduledChange> {
+        let mut changes: Vec<ScheduledChange> = self
+            .all_pages(user)
+            .iter()
+            .flat_map(ScheduledChange::of_page)
+            .collect();
+        changes.sort_by(|a, b| (a.at, &a.name).cmp(&(b.at, &b.name)));
+        changes
+    }
+
+    /// Pages the user can view nested under their parents, siblings sorted
+    /// by their order. A page whose parent is hidden shows at the root.
+    pub fn tree(&self, user: &User) -> Vec<PageNode> {
+        PageNode::tree(self.all_pages(user))
+    }
+
+    /// Change some fields of a page, every value is checked against the
+    /// field declarations of the page template before anything is saved and
+    /// rich text is sanitized
+    pub fn update_page(
+        &self,
+        user: &User,
+        id: &str,
+        mut fields: HashMap<String, Value>,
+    ) -> Result<Module> {
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, id, Right::Edit)?;
+        let mut module = storage_lock.get_page(id).ok_or(Error::PageNotFound)?;
+        let theme = self.theme();
+        validate_fields(
+            &*storage_lock,
+            self.media.as_ref(),
+            &theme,
+            &module,
+            &fields,
+        )?;
+        if let Some(template) = theme.templates.get(&module.template) {
+            template.sanitize_fields(&mut fields);
+        }
+
+        let old = module.clone();
+        module.fields.extend(fields);
+        self.save_draft(&mut *storage_lock, user, id, &old, &module)?;
+        Ok(module)
+    }
+
+    /// Every saved version of a page, oldest first
+    pub fn revisions(&self, user: &User, id: &str) -> Result<Vec<Revision>> {
+        let storage_lock = self.storage.read().unwrap();
+        check(&*storage_lock, user, id, Right::View)?;
+        storage_lock.get_revisions(id)
+    }
+
+    pub fn revision(&self, user: &User, id: &str, number: usize) -> Result<Revision> {
+        self.revisions(user, id)?
+            .into_iter()
+            .find(|revision| revision.number == number)
+            .ok_or(Error::RevisionNotFound)
+    }
+
+    /// Changes made to a page between two of its revisions
+    pub fn diff_revisions(
+        &self,
+        user: &User,
+        id: &str,
+        from: usize,
+        to: usize,
+    ) -> Result<Vec<Change>> {
+        let revisions = self.revisions(user, id)?;
+        let find = |number| {
+            revisions
+                .iter()
+                .find(|revision| revision.number == number)
+                .ok_or(Error::RevisionNotFound)
+        };
+        Ok(diff(&find(from)?.module, &find(to)?.module))
+    }
+
+    /// Make an old revision the draft again, saved as a new revision so the
+    /// versions in between are kept
+    pub fn restore_revision(&self, user: &User, id: &str, number: usize) -> Result<Module> {
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, id, Right::Edit)?;
+        let revision = storage_lock
+            .get_revisions(id)?
+            .into_iter()
+            .find(|revision| revision.number == number)
+            .ok_or(Error::RevisionNotFound)?;
+        let old = storage_lock.get_page(id).ok_or(Error::PageNotFound)?;
+        let theme = self.theme();
+        validate_module(
+            &*storage_lock,
+            self.media.as_ref(),
+            &theme,
+            &revision.module,
+            false,
+        )?;
+        storage_lock.update_page(id, &revision.module, Some(&user.name), self.clock.now())?;
+        self.index_page(&*storage_lock, id);
+        let entry = AuditEntry::new(Some(user), AuditAction::RevisionRestored)
+            .page(id)
+            .summary(None, Some(number.to_string()))
+            .changes(diff(&old, &revision.module));
+        self.log(&mut *storage_lock, entry)?;
+        Ok(revision.module)
+    }
+
+    /// Insert a new module with the defaults of `template` at `path`,
+    /// returns the updated page. Its required fields may stay empty until
+    /// the page is published.
+    pub fn insert_module(
+        &self,
+        user: &User,
+        page_id: &str,
+        path: &str,
+        template: &str,
+    ) -> Result<Module> {
+        let path: ModulePath = path.parse()?;
+        let module = self.theme().get_module_defaults(template)?;
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, page_id, Right::Edit)?;
+        let mut page = storage_lock.get_page(page_id).ok_or(Error::PageNotFound)?;
+
+        self.check_area(&page, path.area(), Some(&module.template))?;
+        validate_module(
+            &*storage_lock,
+            self.media.as_ref(),
+            &self.theme(),
+            &module,
+            false,
+        )?;
+        let old = page.clone();
+        page.insert(&path, module)?;
+        self.save_draft(&mut *storage_lock, user, page_id, &old, &page)?;
+        Ok(page)
+    }
+
+    /// Move a module to another place, in the same page or in another one.
+    /// The destination is resolved once the module is taken out of its area,
+    /// so moving inside an area also reorders its siblings. Gives back the
+    /// draft of the destination page.
+    pub fn move_module(
+        &self,
+        user: &User,
+        page_id: &str,
+        from: &str,
+        to_page_id: &str,
+        to: &str,
+    ) -> Result<Module> {
+        let from: ModulePath = from.parse()?;
+        let to: ModulePath = to.parse()?;
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, page_id, Right::Edit)?;
+        let mut page = storage_lock.get_page(page_id).ok_or(Error::PageNotFound)?;
+        let old = page.clone();
+
+        if page_id == to_page_id {
+            if to.starts_with(&from) {
+                return Err(Error::InvalidModulePath);
+            }
+            let module = page.remove(&from)?;
+            self.check_area(&page, to.area(), Some(&module.template))?;
+            page.insert(&to, module)?;
+            self.save_draft(&mut *storage_lock, user, page_id, &old, &page)?;
+            return Ok(page);
+        }
+
+        check(&*storage_lock, user, to_page_id, Right::Edit)?;
+        let mut to_page = storage_lock
+            .get_page(to_page_id)
+            .ok_or(Error::PageNotFound)?;
+        let old_to_page = to_page.clone();
+        let module = page.remove(&from)?;
+        self.check_area(&to_page, to.area(), Some(&module.template))?;
+        to_page.insert(&to, module)?;
+        self.save_draft(&mut *storage_lock, user, to_page_id, &old_to_page, &to_page)?;
+        self.save_draft(&mut *storage_lock, user, page_id, &old, &page)?;
+        Ok(to_page)
+    }
+
+    /// Reorder the modules of an area, `order` lists the current index of
+    /// every module in its new position
+    pub fn reorder_modules(
+        &self,
+        user: &User,
+        page_id: &str,
+        area: &str,
+        order: &[usize],
+    ) -> Result<Module> {
+        let area: AreaPath = area.parse()?;
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, page_id, Right::Edit)?;
+        let mut page = storage_lock.get_page(page_id).ok_or(Error::PageNotFound)?;
+
+        self.check_area(&page, &area, None)?;
+        let old = page.clone();
+        let modules = page.area_mut(&area)?;
+        let mut sorted = order.to_vec();
+        sorted.sort_unstable();
+        if sorted != (0..modules.len()).collect::<Vec<_>>() {
+            return Err(Error::InvalidModulePath);
+        }
+        let mut taken: Vec<Option<Module>> = modules.drain(..).map(Some).collect();
+        modules.extend(order.iter().filter_map(|index| taken[*index].take()));
+
+        self.save_draft(&mut *storage_lock, user, page_id, &old, &page)?;
+        Ok(page)
+    }
+
+    /// Take the module at `path` out of the page
+    pub fn remove_module(&self, user: &User, page_id: &str, path: &str) -> Result<Module> {
+        let path: ModulePath = path.parse()?;
+        let mut storage_lock = self.storage.write().unwrap();
+        check(&*storage_lock, user, page_id, Right::Edit)?;
+        let mut page = storage_lock.get_page(page_id).ok_or(Error::PageNotFound)?;
+
+        let old = page.clone();
+        let module = page.remove(&path)?;
+        self.save_draft(&mut *storage_lock, user, page_id, &old, &page)?;
+        Ok(module)
+    }
+
+    /// List the component templates which can be placed in an area of a page
+    pub fn accepted_components(
+        &self,
+        user: &User,
+        page_id: &str,
+        area: &str,
+    ) -> Result<Vec<String>> {
+        let area: AreaPath = area.parse()?;
+        let storage_lock = self.storage.read().unwrap();
+        check(&*storage_lock, user, page_id, Right::View)?;
+        let page = storage_lock.get_page(page_id).ok_or(Error::PageNotFound)?;
+        drop(storage_lock);
+
+        let owner = page.area_owner(&area)?;
+        self.theme()
+            .accepted_components(&owner.template, area.name())
+    }
+
+    /// Save the draft of a page and log what the user changed in it
+    fn save_draft(
+        &self,
+        storage: &mut dyn Store,
+        user: &User,
+        id: &str,
+        old: &Module,
+        module: &Module,
+    ) -> Result<()> {
+        storage.update_page(id, module, Some(&user.name), self.clock.now())?;
+        self.index_page(storage, id);
+        let entry = AuditEntry::new(Some(user), AuditAction::PageEdited)
+            .page(id)
+            .changes(diff(old, module));
+        self.log(storage, entry)
+    }
+
+    /// Follow a change of the draft or of the published version of a page in
+    /// the reference index
+    fn index_page(&self, storage: &dyn Store, id: &str) {
+        let theme = self.theme();
+        self.references.write().unwrap().update(storage, &theme, id);
+    }
+
+    /// Fields of drafts and published pages holding the id of a page, or of
+    /// a media when `media` is set
+    fn references_to(&self, storage: &dyn Store, id: &str, media: bool) -> Result<Vec<Reference>> {
+        let theme = self.theme();
+        let mut index_lock = self.references.write().unwrap();
+        index_lock.references_to(storage, &theme, id, media)
+    }
+
+    /// Append an entry to the audit log, dated by the site clock
+    fn log(&self, storage: &mut dyn Store, mut entry: AuditEntry) -> Result<()> {
+        entry.date = self.clock.now();
+        storage.append_audit(&entry)
+    }
+
+    /// Log a change made outside of the storage, like a media upload
+    pub fn record(&self, entry: AuditEntry) -> Result<()> {
+        let mut storage_lock = self.storage.write().unwrap();
+        self.log(&mut *storage_lock, entry)
+    }
+
+    /// Create an account and log it, only admins manage accounts
+    pub fn create_user(
+        &self,
+        accounts: &Accounts,
+        user: &User,
+        name: &str,
+        password: &str,
+        role: Role,
+    ) -> Result<User> {
+        if user.role != Role::Admin {
+            return Err(Error::Forbidden);
         }
-        storage_lock.delete_page(id)
+        let mut storage_lock = self.storage.write().unwrap();
+        let created = accounts.create_user(name, password, role)?;
+        let entry = AuditEntry::new(Some(user), AuditAction::UserCreated)
+            .account(&created.name)
+            .summary(None, Some(describe_account(&created)));
+        self.log(&mut *storage_lock, entry)?;
+        Ok(created)
     }
 
+    /// Change an account and log it, a new password only shows as such
+    pub fn update_user(
+        &self,
+        accounts: &Accounts,
+        user: &User,
+        name: &str,
+        changes: UserChanges,
+    ) -> Result<User> {
+        if user.role != Role::Admin {
+            return Err(Error::Forbidden);
+        }
+        let mut storage_lock = self.storage.write().unwrap();
+        let before = accounts.get_user(name).ok_or(Error::UserNotFound)?;
+        let new_password = changes.password.is_some();
+        let updated = accounts.update_user(name, changes)?;
+        let mut after = describe_account(&updated);
+        if new_password {
+            after.push_str(", new password");
+        }
+        let entry = AuditEntry::new(Some(user), AuditAction::UserUpdated)
+            .account(&updated.name)
+            .summary(Some(describe_account(&before)), Some(after));
+        self.log(&mut *storage_lock, entry)?;
+        Ok(updated)
+    }
+
+    /// Remove an account and log it
+    pub fn delete_user(&self, accounts: &Accounts, user: &User, name: &str) -> Result<User> {
+        if user.role != Role::Admin {
+            return Err(Error::Forbidden);
+        }
+        let mut storage_lock = self.storage.write().unwrap();
+        let deleted = accounts.delete_user(name)?;
+        let entry = AuditEntry::new(Some(user), AuditAction::UserDeleted)
+            .account(&deleted.name)
+            .summary(Some(describe_account(&deleted)), None);
+        self.log(&mut *storage_lock, entry)?;
+        Ok(deleted)
+    }
+
+    /// Entries of the audit log matching the filter, oldest first. Only
+    /// admins can read it.
+    pub fn audit_log(&self, user: &User, filter: &AuditFilter) -> Result<Vec<AuditEntry>> {
+        if user.role != Role::Admin {
+            return Err(Error::Forbidden);
+        }
+        let storage_lock = self.storage.read().unwrap();
+        let entries = storage_lock.get_audit()?;
+        Ok(entries
+            .into_iter()
+            .filter(|entry| filter.matches(entry))
+            .collect())
+    }
+
+    /// Make sure the area is declared by the template of the module owning it
+    /// and, when a module is placed in it, that the area accepts its template
+    fn check_area(&self, page: &Module, area: &AreaPath, placed: Option<&str>) -> Result<()> {
+        let owner = page.area_owner(area)?;
+        let theme = self.theme();
+        let declaration = theme.get_area(&owner.template, area.name())?;
+        match placed {
+            Some(template) if !declaration.accepts(template) => Err(Error::ComponentNotAccepted {
+                area: area.to_string(),
+                template: template.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Pages visible now, the ones scheduled outside of their publication
+    /// window are left out
     pub fn summary(&self) -> Vec<Page> {
-        let storage_lock = self.storage.lock().unwrap();
-        storage_lock.summary()
+        let now = self.clock.now();
+        let storage_lock = self.storage.read().unwrap();
+        storage_lock
+            .summary()
+            .into_iter()
+            .filter(|page| page.in_window(now))
+            .collect()
     }
 
+    /// Every page the user can view, whatever their schedule
+    pub fn all_pages(&self, user: &User) -> Vec<Page> {
+        let storage_lock = self.storage.read().unwrap();
+        storage_lock
+            .summary()
+            .into_iter()
+            .filter(|page| is_allowed(&*storage_lock, user, &page.id, Right::View).unwrap_or(false))
+            .collect()
+    }
+
+    /// Replace the access entries of a page, only admins manage them. The
+    /// entries apply to the page and to the pages under it which have none.
+    pub fn set_access(&self, user: &User, id: &str, access: Vec<AccessEntry>) -> Result<Page> {
+        if user.role != Role::Admin {
+            return Err(Error::Forbidden);
+        }
+        let mut storage_lock = self.storage.write().unwrap();
+        if !storage_lock.page_exists(id) {
+            return Err(Error::PageNotFound);
+        }
+        let before = storage_lock
+            .get_page_info(id)
+            .map(|page| describe_access(&page.access));
+        let after = describe_access(&access);
+        storage_lock.set_access(id, access)?;
+        let entry = AuditEntry::new(Some(user), AuditAction::AccessChanged)
+            .page(id)
+            .summary(before, Some(after));
+        self.log(&mut *storage_lock, entry)?;
+        storage_lock.get_page_info(id).ok_or(Error::PageNotFound)
+    }
+
+    /// Render the published version of a page
     pub fn render_page(&self, name: &str) -> Result<String> {
-        let storage_lock = self.storage.lock().unwrap();
+        let storage_lock = self.storage.read().unwrap();
         let page = storage_lock
-            .get_page_by_name(name)
+            .summary()
+            .into_iter()
+            .find(|page| page.name == name)
             .ok_or(Error::PageNotFound)?;
-        let mut renderer_lock = self.renderer.lock().unwrap();
-        renderer_lock.load(&self.theme)?;
+        if !page.in_window(self.clock.now()) {
+            return Err(Error::PageNotPublished);
+        }
+        let mut module = storage_lock
+            .get_published(&page.id)
+            .ok_or(Error::PageNotPublished)?;
+        resolve_module(
+            &*storage_lock,
+            self.media.as_ref(),
+            &self.theme(),
+            self.clock.now(),
+            &mut module,
+        );
+        drop(storage_lock);
+
+        let renderer_lock = self.renderer.read().unwrap();
+        renderer_lock.render_module(&module)
+    }
+
+    /// Render the draft of a page, as it would look once published
+    pub fn preview_page(&self, user: &User, id: &str) -> Result<String> {
+        let storage_lock = self.storage.read().unwrap();
+        check(&*storage_lock, user, id, Right::View)?;
+        let mut module = storage_lock.get_page(id).ok_or(Error::PageNotFound)?;
+        resolve_module(
+            &*storage_lock,
+            self.media.as_ref(),
+            &self.theme(),
+            self.clock.now(),
+            &mut module,
+        );
+        drop(storage_lock);
+
+        let renderer_lock = self.renderer.read().unwrap();
+        renderer_lock.render_module(&module)
+    }
 
-        let html = renderer_lock.render_module(&page)?;
-        Ok(html)
+    /// Find the page served at an url path, made of the slugs of the page and
+    /// its ancestors, or at its name. The root of the site is the page with
+    /// the `index` slug.
+    pub fn find_page(&self, path: &str) -> Option<Page> {
+        let path = match path.trim_matches('/') {
+            "" => INDEX_SLUG,
+            path => path,
+        };
+        let summary = self.summary();
+        let index = summary
+            .iter()
+            .position(|page| page.path == path)
+            .or_else(|| summary.iter().position(|page| page.name == path))?;
+        summary.into_iter().nth(index)
+    }
+
+    /// Render the not found page of the theme, a bare page when the theme
+    /// does not have one
+    pub fn render_not_found(&self) -> Result<String> {
+        let theme = self.theme();
+        if !theme.templates.contains_key(NOT_FOUND_TEMPLATE) {
+            return Ok(DEFAULT_NOT_FOUND.to_string());
+        }
+        let module = theme.get_module_defaults(NOT_FOUND_TEMPLATE)?;
+        let renderer_lock = self.renderer.read().unwrap();
+        renderer_lock.render_module(&module)
+    }
+
+    /// Render every published page inside its publication window as
+    /// `<path>/index.html` inside `output_dir`, the `index` page as
+    /// `index.html` since it is served at the root, next to the files of the
+    /// media library in `media`.
+    /// Pages whose module and templates did not change since the last build
+    /// into the same folder are not rendered again. Pages sharing a folder,
+    /// or built into `media`, are reported as failures.
+    pub fn build(&self, output_dir: &Path) -> Result<BuildReport> {
+        let now = self.clock.now();
+        let storage_lock = self.storage.read().unwrap();
+        let generation = self.built.read().unwrap().generation();
+        let theme = self.theme();
+        let mut modules = storage_lock.get_published_pages()?;
+        for module in modules.values_mut() {
+            resolve_module(&*storage_lock, self.media.as_ref(), &theme, now, module);
+        }
+        let pages = storage_lock
+            .summary()
+            .into_iter()
+            .filter(|page| modules.contains_key(&page.id) && page.in_window(now))
+            .collect();
+        drop(storage_lock);
+
+        let renderer_lock = self.renderer.read().unwrap();
+        let report = build_pages(
+            &theme,
+            &*renderer_lock,
+            pages,
+            modules,
+            output_dir,
+            &[MEDIA_FOLDER],
+        )?;
+        if let Some(media) = &self.media {
+            media.export(&output_dir.join(MEDIA_FOLDER))?;
+        }
+        self.built.write().unwrap().update(generation, &report);
+        Ok(report)
+    }
+
+    /// Html file of a page written by the last build, only while nothing
+    /// changed what the page looks like online since
+    pub fn built_file(&self, id: &str) -> Option<PathBuf> {
+        self.built.read().unwrap().get(id)
+    }
+
+    /// Forget the files of the last build, a change made them stale
+    fn outdate_builds(&self) {
+        self.built.write().unwrap().outdate();
+    }
+
+    fn media_library(&self) -> Result<&MediaLibrary> {
+        self.media.as_ref().ok_or(Error::NoMediaLibrary)
+    }
+
+    /// Folder holding the files of the media library, if the site has one.
+    /// Only the files are there, the index of the library is kept out.
+    pub fn media_folder(&self) -> Option<PathBuf> {
+        self.media.as_ref().map(MediaLibrary::files_folder)
+    }
+
+    /// Add a file to the media library, uploading a file already there
+    /// gives the existing media back
+    pub fn upload_media(
+        &self,
+        user: &User,
+        name: &str,
+        content: &[u8],
+        alt: &str,
+    ) -> Result<Media> {
+        let media =
+            self.media_library()?
+                .add(name, content, alt, Some(&user.name), self.clock.now())?;
+        let entry = AuditEntry::new(Some(user), AuditAction::MediaUploaded)
+            .media(&media.id)
+            .summary(None, Some(media.name.to_owned()));
+        self.record(entry)?;
+        Ok(media)
+    }
+
+    /// Media of the library matching the filter, most recent first
+    pub fn media_list(&self, filter: &MediaFilter) -> Result<Vec<Media>> {
+        Ok(self.media_library()?.list(filter))
+    }
+
+    pub fn media(&self, id: &str) -> Result<Media> {
+        self.media_library()?.get(id).ok_or(Error::MediaNotFound)
+    }
+
+    /// Change the alternative text of a media, shown by every page using it.
+    /// It takes the right to edit pages on the whole site.
+    pub fn update_media(&self, user: &User, id: &str, alt: &str) -> Result<Media> {
+        let library = self.media_library()?;
+        let mut storage_lock = self.storage.write().unwrap();
+        check_site(&*storage_lock, user, Right::Edit)?;
+        let before = library.get(id).ok_or(Error::MediaNotFound)?.alt;
+        let media = library.update_alt(id, alt)?;
+        self.outdate_builds();
+        let entry = AuditEntry::new(Some(user), AuditAction::MediaUpdated)
+            .media(id)
+            .summary(Some(before), Some(media.alt.to_owned()));
+        self.log(&mut *storage_lock, entry)?;
+        Ok(media)
+    }
+
+    /// Remove a media from the library, refused while a draft or a
+    /// published page still uses it. It takes the right to delete pages on
+    /// the whole site.
+    pub fn delete_media(&self, user: &User, id: &str) -> Result<Media> {
+        let library = self.media_library()?;
+        let mut storage_lock = self.storage.write().unwrap();
+        check_site(&*storage_lock, user, Right::Delete)?;
+        let references = self.references_to(&*storage_lock, id, true)?;
+        if !references.is_empty() {
+            return Err(Error::MediaInUse(references));
+        }
+        let media = library.remove(id)?;
+        self.outdate_builds();
+        let entry = AuditEntry::new(Some(user), AuditAction::MediaDeleted)
+            .media(id)
+            .summary(Some(media.name.to_owned()), None);
+        self.log(&mut *storage_lock, entry)?;
+        Ok(media)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "snake_case")]
-struct Configuration {
-    storage: Option<String>,
-    renderer: Option<String>,
-    #[serde(default = "default_theme")]
-    theme: String,
+/// Look for every problem of the theme in `path` without building a site
+pub fn check_theme(path: &str) -> Result<Vec<Diagnostic>> {
+    let (_, diagnostics) = Theme::load(&PathBuf::from(path))?;
+    Ok(diagnostics)
 }
 
-fn default_theme() -> String {
-    "theme".to_string()
+/// Publication window of a page for the audit log, `-` for a missing end
+fn describe_window(
+    publish_at: Option<DateTime<Utc>>,
+    unpublish_at: Option<DateTime<Utc>>,
+) -> String {
+    let describe = |at: Option<DateTime<Utc>>| at.map_or("-".to_string(), |at| at.to_rfc3339());
+    format!("{} / {}", describe(publish_at), describe(unpublish_at))
+}
+
+/// An account for the audit log, its role and groups
+fn describe_account(user: &User) -> String {
+    format!("{:?}, groups: {}", user.role, user.groups.join(",")).to_lowercase()
+}
+
+/// Access entries of a page for the audit log, `user:name view,edit; ...`
+fn describe_access(access: &[AccessEntry]) -> String {
+    access
+        .iter()
+        .map(|entry| {
+            let subject = match &entry.subject {
+                Subject::User(name) => format!("user:{}", name),
+                Subject::Group(name) => format!("group:{}", name),
+            };
+            let rights: Vec<String> = entry
+                .rights
+                .iter()
+                .map(|right| format!("{:?}", right).to_lowercase())
+                .collect();
+            format!("{} {}", subject, rights.join(","))
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
 }
 
 pub struct SiteBuilder {
-    storage: Option<Arc<Mutex<dyn Store>>>,
-    renderer: Option<Arc<Mutex<dyn Render>>>,
+    storage: Option<Arc<RwLock<dyn Store>>>,
+    renderer: Option<Arc<RwLock<dyn Render>>>,
     theme: Option<Theme>,
+    clock: Option<Arc<dyn Clock>>,
+    media: Option<MediaLibrary>,
 }
 
 impl SiteBuilder {
@@ -106,18 +1093,32 @@ impl SiteBuilder {
             storage: None,
             renderer: None,
             theme: None,
+            clock: None,
+            media: None,
         }
     }
 
+    /// Use another clock than the system one to apply schedules
+    pub fn add_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
     pub fn add_yaml_storage(mut self, path: &str) -> Result<Self> {
         let storage = YamlStorage::try_from(path)?;
-        self.storage = Some(Arc::new(Mutex::new(storage)));
+        self.storage = Some(Arc::new(RwLock::new(storage)));
+        Ok(self)
+    }
+
+    /// Keep uploaded files in `folder`, created when missing
+    pub fn add_media(mut self, folder: &str) -> Result<Self> {
+        self.media = Some(MediaLibrary::load(folder)?);
         Ok(self)
     }
 
     pub fn add_tera_renderer(mut self) -> Result<Self> {
         let renderer = TeraRenderer::default();
-        self.renderer = Some(Arc::new(Mutex::new(renderer)));
+        self.renderer = Some(Arc::new(RwLock::new(renderer)));
         Ok(self)
     }
 
@@ -126,7 +1127,7 @@ impl SiteBuilder {
         Ok(self)
     }
 
-    pub fn build(self) -> Site {
+    pub fn build(self) -> Result<Site> {
         let theme = self
             .theme
             .expect("Could not build site because of missing theme");
@@ -138,7 +1139,12 @@ impl SiteBuilder {
         let storage = self
             .storage
             .expect("Could not build site because of missing storage");
-        Site::new(theme, Arc::clone(&storage), Arc::clone(&renderer))
+        let mut site = Site::new(theme, Arc::clone(&storage), Arc::clone(&renderer))?;
+        if let Some(clock) = self.clock {
+            site.clock = clock;
+        }
+        site.media = self.media;
+        Ok(site)
     }
 }
 
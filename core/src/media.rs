@@ -0,0 +1,380 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::RwLock,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+
+/// File listing the media of a library, kept out of the folder of the
+/// files since only that one is served
+const INDEX_FILE: &str = "media.yml";
+/// Folder of a library holding the uploaded files
+const FILES_FOLDER: &str = "files";
+/// Extensions a plain text file keeps, others are stored as `txt`
+const TEXT_EXTENSIONS: [&str; 3] = ["txt", "csv", "md"];
+/// Documents stored as zip archives, by extension
+const ZIP_DOCUMENTS: [&str; 7] = ["zip", "docx", "xlsx", "pptx", "odt", "ods", "odp"];
+
+/// An uploaded file, named after the hash of its content so uploading it
+/// again gives the same media
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Media {
+    pub id: String,
+    /// Name of the file when it was uploaded
+    pub name: String,
+    /// Name of the file in the library folder
+    pub file: String,
+    pub mime: String,
+    /// In bytes
+    pub size: u64,
+    /// Only known for images
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// Text describing an image to who cannot see it
+    #[serde(default)]
+    pub alt: String,
+    pub uploaded: DateTime<Utc>,
+    pub author: Option<String>,
+}
+
+impl Media {
+    pub fn is_image(&self) -> bool {
+        self.mime.starts_with("image/")
+    }
+
+    /// Where the site serves the file
+    pub fn url(&self) -> String {
+        format!("/media/{}", self.file)
+    }
+}
+
+/// Criteria a media must all meet to be listed
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MediaFilter {
+    /// Part of the name or of the alt text, whatever the case
+    pub search: Option<String>,
+    /// Start of the mime type, like `image/`
+    pub mime: Option<String>,
+}
+
+impl MediaFilter {
+    pub fn matches(&self, media: &Media) -> bool {
+        let search = self.search.as_deref().map(str::to_lowercase);
+        search.is_none_or(|search| {
+            media.name.to_lowercase().contains(&search)
+                || media.alt.to_lowercase().contains(&search)
+        }) && self
+            .mime
+            .as_deref()
+            .is_none_or(|mime| media.mime.starts_with(mime))
+    }
+}
+
+/// Uploaded files stored in the `files` folder of a library, next to the
+/// yaml index of their metadata
+#[derive(Debug)]
+pub struct MediaLibrary {
+    folder: PathBuf,
+    media: RwLock<BTreeMap<String, Media>>,
+}
+
+impl MediaLibrary {
+    /// Open the library of a folder, created when missing. Files of a
+    /// library stored next to its index are moved to their own folder.
+    pub fn load(folder: &str) -> Result<Self> {
+        let folder = PathBuf::from(folder);
+        fs::create_dir_all(folder.join(FILES_FOLDER))?;
+        let media: BTreeMap<String, Media> = match fs::File::open(folder.join(INDEX_FILE)) {
+            Ok(reader) => serde_yaml::from_reader(reader)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(error) => return Err(error.into()),
+        };
+        for media in media.values() {
+            let old = folder.join(&media.file);
+            let new = folder.join(FILES_FOLDER).join(&media.file);
+            if old.is_file() && !new.exists() {
+                fs::rename(old, new)?;
+            }
+        }
+        Ok(Self {
+            folder,
+            media: RwLock::new(media),
+        })
+    }
+
+    /// Folder of the uploaded files, the only part of the library to serve
+    pub fn files_folder(&self) -> PathBuf {
+        self.folder.join(FILES_FOLDER)
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<Media> {
+        self.media.read().unwrap().get(id).cloned()
+    }
+
+    /// Media meeting the filter, most recent first
+    pub(crate) fn list(&self, filter: &MediaFilter) -> Vec<Media> {
+        let mut media: Vec<Media> = self
+            .media
+            .read()
+            .unwrap()
+            .values()
+            .filter(|media| filter.matches(media))
+            .cloned()
+            .collect();
+        media.sort_by(|a, b| (b.uploaded, &a.name).cmp(&(a.uploaded, &b.name)));
+        media
+    }
+
+    /// Store a file and its metadata, a file already in the library is
+    /// returned as it is
+    pub(crate) fn add(
+        &self,
+        name: &str,
+        content: &[u8],
+        alt: &str,
+        author: Option<&str>,
+        date: DateTime<Utc>,
+    ) -> Result<Media> {
+        if content.is_empty() {
+            return Err(Error::EmptyFile);
+        }
+        let id = to_hex(&Sha256::digest(content));
+        let mut media_lock = self.media.write().unwrap();
+        if let Some(media) = media_lock.get(&id) {
+            return Ok(media.clone());
+        }
+
+        let (mime, extension) = sniff(name, content)?;
+        let file = format!("{}.{}", id, extension);
+        let (width, height) = match imagesize::blob_size(content) {
+            Ok(size) => (Some(size.width as u32), Some(size.height as u32)),
+            Err(..) => (None, None),
+        };
+        let media = Media {
+            id: id.to_owned(),
+            name: name.to_owned(),
+            mime,
+            file,
+            size: content.len() as u64,
+            width,
+            height,
+            alt: alt.to_owned(),
+            uploaded: date,
+            author: author.map(str::to_owned),
+        };
+        fs::write(self.files_folder().join(&media.file), content)?;
+        media_lock.insert(id, media.clone());
+        self.save(&media_lock)?;
+        Ok(media)
+    }
+
+    pub(crate) fn update_alt(&self, id: &str, alt: &str) -> Result<Media> {
+        let mut media_lock = self.media.write().unwrap();
+        let media = media_lock.get_mut(id).ok_or(Error::MediaNotFound)?;
+        media.alt = alt.to_owned();
+        let media = media.clone();
+        self.save(&media_lock)?;
+        Ok(media)
+    }
+
+    pub(crate) fn remove(&self, id: &str) -> Result<Media> {
+        let mut media_lock = self.media.write().unwrap();
+        let media = media_lock.remove(id).ok_or(Error::MediaNotFound)?;
+        match fs::remove_file(self.files_folder().join(&media.file)) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error.into()),
+            _ => {}
+        }
+        self.save(&media_lock)?;
+        Ok(media)
+    }
+
+    /// Copy every file of the library into a folder, leaving the ones
+    /// already there untouched since their name is their content
+    pub(crate) fn export(&self, folder: &Path) -> Result<()> {
+        fs::create_dir_all(folder)?;
+        for media in self.media.read().unwrap().values() {
+            let target = folder.join(&media.file);
+            if !target.is_file() {
+                fs::copy(self.files_folder().join(&media.file), target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn save(&self, media: &BTreeMap<String, Media>) -> Result<()> {
+        let file = fs::File::create(self.folder.join(INDEX_FILE))?;
+        serde_yaml::to_writer(file, media)?;
+        Ok(())
+    }
+}
+
+/// Mime type and extension of an uploaded file from its content, only
+/// images, pdf, office documents and plain text are accepted. Html, svg
+/// and other types a browser could run scripts from are refused.
+fn sniff(name: &str, content: &[u8]) -> Result<(String, String)> {
+    use imagesize::ImageType;
+
+    let extension = extension(name).unwrap_or_default();
+    let (mime, extension) = match imagesize::image_type(content) {
+        Ok(ImageType::Png) => ("image/png", "png"),
+        Ok(ImageType::Jpeg) => ("image/jpeg", "jpg"),
+        Ok(ImageType::Gif) => ("image/gif", "gif"),
+        Ok(ImageType::Webp) => ("image/webp", "webp"),
+        _ if content.starts_with(b"%PDF-") => ("application/pdf", "pdf"),
+        _ if content.starts_with(b"PK\x03\x04") => {
+            let extension = match ZIP_DOCUMENTS.contains(&extension.as_str()) {
+                true => extension.as_str(),
+                false => "zip",
+            };
+            let mime = mime_guess::from_ext(extension).first_raw();
+            (mime.unwrap_or("application/zip"), extension)
+        }
+        _ if is_text(content) => match extension.as_str() {
+            "csv" => ("text/csv", "csv"),
+            extension if TEXT_EXTENSIONS.contains(&extension) => ("text/plain", extension),
+            _ => ("text/plain", "txt"),
+        },
+        _ => return Err(Error::UnsupportedMedia),
+    };
+    Ok((mime.to_owned(), extension.to_owned()))
+}
+
+/// Text without markup a browser could render as a page
+fn is_text(content: &[u8]) -> bool {
+    let text = match std::str::from_utf8(content) {
+        Ok(text) => text,
+        Err(..) => return false,
+    };
+    let start = text.trim_start().to_lowercase();
+    !text.contains('\0')
+        && !["<!doctype", "<html", "<svg", "<?xml", "<script"]
+            .iter()
+            .any(|tag| start.starts_with(tag))
+}
+
+/// Lowercase extension of a file name, when it only has letters and digits
+fn extension(name: &str) -> Option<String> {
+    Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| !extension.is_empty())
+        .filter(|extension| extension.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(str::to_lowercase)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    mod library {
+        use crate::media::{MediaFilter, MediaLibrary};
+        use chrono::Utc;
+
+        /// Smallest valid png, a single transparent pixel
+        const PIXEL: &[u8] = &[
+            0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48,
+            0x44, 0x52, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00,
+            0x00, 0x1f, 0x15, 0xc4, 0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78,
+            0x9c, 0x63, 0x60, 0x00, 0x02, 0x00, 0x00, 0x05, 0x00, 0x01, 0x7a, 0x5e, 0xab, 0x3f,
+            0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+        ];
+
+        #[test]
+        fn add_and_find_media() {
+            let dir = tempfile::tempdir().unwrap();
+            let library = MediaLibrary::load(dir.path().to_str().unwrap()).unwrap();
+
+            let pixel = library
+                .add("Pixel.PNG", PIXEL, "a dot", Some("admin"), Utc::now())
+                .unwrap();
+            assert_eq!(pixel.file, format!("{}.png", pixel.id));
+            assert_eq!(pixel.mime, "image/png");
+            assert_eq!((pixel.width, pixel.height), (Some(1), Some(1)));
+            assert!(dir.path().join("files").join(&pixel.file).is_file());
+
+            let again = library
+                .add("copy.png", PIXEL, "", None, Utc::now())
+                .unwrap();
+            assert_eq!(again, pixel);
+
+            let notes = library
+                .add("notes", b"some text", "", None, Utc::now())
+                .unwrap();
+            assert_eq!(notes.mime, "text/plain");
+            assert_eq!(notes.file, format!("{}.txt", notes.id));
+            assert_eq!(notes.width, None);
+
+            let images = MediaFilter {
+                mime: Some("image/".into()),
+                ..Default::default()
+            };
+            assert_eq!(library.list(&images), vec![pixel.clone()]);
+            let search = MediaFilter {
+                search: Some("DOT".into()),
+                ..Default::default()
+            };
+            assert_eq!(library.list(&search).len(), 1);
+
+            let reloaded = MediaLibrary::load(dir.path().to_str().unwrap()).unwrap();
+            assert_eq!(reloaded.get(&pixel.id), Some(pixel.clone()));
+            reloaded.remove(&pixel.id).unwrap();
+            assert!(!dir.path().join("files").join(&pixel.file).exists());
+            assert!(reloaded.get(&pixel.id).is_none());
+        }
+
+        #[test]
+        fn sniff_uploaded_files() {
+            use crate::error::Error;
+
+            let dir = tempfile::tempdir().unwrap();
+            let library = MediaLibrary::load(dir.path().to_str().unwrap()).unwrap();
+            let add = |name: &str, content: &[u8]| library.add(name, content, "", None, Utc::now());
+
+            let disguised = add("pixel.html", PIXEL).unwrap();
+            assert_eq!(disguised.file, format!("{}.png", disguised.id));
+            assert_eq!(
+                add("report.pdf", b"%PDF-1.7 ...").unwrap().mime,
+                "application/pdf"
+            );
+            let table = add("table.CSV", b"a,b\n1,2\n").unwrap();
+            assert_eq!(
+                (table.mime.as_str(), table.file.ends_with(".csv")),
+                ("text/csv", true)
+            );
+            let page = add("notes.txt", b"<html><script>alert(1)</script></html>");
+            assert!(matches!(page, Err(Error::UnsupportedMedia)));
+            let svg = add(
+                "logo.svg",
+                b"<?xml version=\"1.0\"?><svg onload=\"alert(1)\"/>",
+            );
+            assert!(matches!(svg, Err(Error::UnsupportedMedia)));
+            assert!(matches!(
+                add("tool.exe", b"MZ\x90\x00\x03\x00"),
+                Err(Error::UnsupportedMedia)
+            ));
+        }
+
+        #[test]
+        fn move_files_out_of_the_index_folder() {
+            let dir = tempfile::tempdir().unwrap();
+            let library = MediaLibrary::load(dir.path().to_str().unwrap()).unwrap();
+            let pixel = library
+                .add("pixel.png", PIXEL, "", None, Utc::now())
+                .unwrap();
+            let served = dir.path().join("files").join(&pixel.file);
+            std::fs::rename(&served, dir.path().join(&pixel.file)).unwrap();
+
+            MediaLibrary::load(dir.path().to_str().unwrap()).unwrap();
+            assert!(served.is_file());
+            assert!(!dir.path().join(&pixel.file).exists());
+        }
+    }
+}
@@ -1,11 +1,18 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    str::FromStr,
+};
 
 use serde::{Deserialize, Serialize};
 
-use crate::theme::Field;
+use crate::{
+    error::{Error, Result},
+    theme::{Field, FieldDefinition},
+};
 use serde_yaml::{Number, Value};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Module {
     pub template: String,
     pub fields: HashMap<String, Value>,
@@ -20,14 +27,285 @@ impl Module {
             areas: HashMap::new(),
         }
     }
+
+    /// Name of every template used by this module and the modules in its areas
+    pub(crate) fn templates(&self) -> BTreeSet<&str> {
+        let mut templates = BTreeSet::from([self.template.as_str()]);
+        for module in self.areas.values().flatten() {
+            templates.extend(module.templates());
+        }
+        templates
+    }
+
+    fn module(&self, path: &[(String, usize)]) -> Result<&Module> {
+        match path.split_first() {
+            None => Ok(self),
+            Some(((area, index), rest)) => self
+                .areas
+                .get(area)
+                .and_then(|modules| modules.get(*index))
+                .ok_or(Error::ModuleNotFound)?
+                .module(rest),
+        }
+    }
+
+    fn module_mut(&mut self, path: &[(String, usize)]) -> Result<&mut Module> {
+        match path.split_first() {
+            None => Ok(self),
+            Some(((area, index), rest)) => self
+                .areas
+                .get_mut(area)
+                .and_then(|modules| modules.get_mut(*index))
+                .ok_or(Error::ModuleNotFound)?
+                .module_mut(rest),
+        }
+    }
+
+    /// Module owning the area, the area itself may not be created yet
+    pub(crate) fn area_owner(&self, area: &AreaPath) -> Result<&Module> {
+        self.module(&area.modules)
+    }
+
+    pub(crate) fn area_mut(&mut self, area: &AreaPath) -> Result<&mut Vec<Module>> {
+        let owner = self.module_mut(&area.modules)?;
+        Ok(owner.areas.entry(area.name.to_owned()).or_default())
+    }
+
+    pub(crate) fn get(&self, path: &ModulePath) -> Result<&Module> {
+        self.area_owner(&path.area)?
+            .areas
+            .get(&path.area.name)
+            .and_then(|modules| modules.get(path.index))
+            .ok_or(Error::ModuleNotFound)
+    }
+
+    /// Insert a module at the path, the index may be the length of the area
+    pub(crate) fn insert(&mut self, path: &ModulePath, module: Module) -> Result<()> {
+        let modules = self.area_mut(&path.area)?;
+        if path.index > modules.len() {
+            return Err(Error::ModuleNotFound);
+        }
+        modules.insert(path.index, module);
+        Ok(())
+    }
+
+    pub(crate) fn remove(&mut self, path: &ModulePath) -> Result<Module> {
+        self.get(path)?;
+        let modules = self.area_mut(&path.area)?;
+        Ok(modules.remove(path.index))
+    }
+}
+
+/// Location of an area inside a page, written `area` for an area of the page
+/// itself or `area/0/inner_area` for an area of a nested module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AreaPath {
+    modules: Segments,
+    name: String,
+}
+
+impl AreaPath {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Location of a module inside a page, written `area_1/0/inner_area/2`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModulePath {
+    area: AreaPath,
+    index: usize,
+}
+
+impl ModulePath {
+    pub fn area(&self) -> &AreaPath {
+        &self.area
+    }
+
+    /// Whether the path points to this module or to a module nested in it
+    pub(crate) fn starts_with(&self, other: &ModulePath) -> bool {
+        let mut segments = self.area.modules.clone();
+        segments.push((self.area.name.to_owned(), self.index));
+        let mut prefix = other.area.modules.clone();
+        prefix.push((other.area.name.to_owned(), other.index));
+        segments.starts_with(&prefix)
+    }
+}
+
+/// Areas and indexes leading to a module
+type Segments = Vec<(String, usize)>;
+
+fn parse_segments(path: &str) -> Result<(Segments, Option<&str>)> {
+    let mut parts = path.trim_matches('/').split('/');
+    let mut segments = vec![];
+    while let Some(area) = parts.next() {
+        if area.is_empty() {
+            return Err(Error::InvalidModulePath);
+        }
+        match parts.next() {
+            Some(index) => {
+                let index = index.parse().map_err(|_| Error::InvalidModulePath)?;
+                segments.push((area.to_string(), index));
+            }
+            None => return Ok((segments, Some(area))),
+        }
+    }
+    Ok((segments, None))
+}
+
+impl FromStr for AreaPath {
+    type Err = Error;
+
+    fn from_str(path: &str) -> Result<Self> {
+        match parse_segments(path)? {
+            (modules, Some(name)) => Ok(Self {
+                modules,
+                name: name.to_string(),
+            }),
+            _ => Err(Error::InvalidModulePath),
+        }
+    }
+}
+
+impl FromStr for ModulePath {
+    type Err = Error;
+
+    fn from_str(path: &str) -> Result<Self> {
+        let (mut modules, rest) = parse_segments(path)?;
+        match (modules.pop(), rest) {
+            (Some((name, index)), None) => Ok(Self {
+                area: AreaPath { modules, name },
+                index,
+            }),
+            _ => Err(Error::InvalidModulePath),
+        }
+    }
+}
+
+impl fmt::Display for AreaPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (area, index) in &self.modules {
+            write!(f, "{}/{}/", area, index)?;
+        }
+        write!(f, "{}", self.name)
+    }
+}
+
+impl fmt::Display for ModulePath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.area, self.index)
+    }
 }
 
 impl From<&Field> for Value {
     fn from(field: &Field) -> Self {
-        match *field {
-            Field::String => Self::String(String::default()),
+        match field {
+            Field::String
+            | Field::Text
+            | Field::Markdown
+            | Field::RichText
+            | Field::Date
+            | Field::Datetime
+            | Field::Url
+            | Field::Email
+            | Field::PageRef(..)
+            | Field::Image
+            | Field::File => Self::String(String::default()),
             Field::Number => Self::Number(Number::from(usize::default())),
             Field::Boolean => Self::Bool(false),
+            Field::Color => Self::String("#000000".to_string()),
+            Field::Select(options) => Self::String(options.first().cloned().unwrap_or_default()),
+            Field::List(..) => Self::Sequence(vec![]),
+            Field::Object(fields) => Self::Mapping(
+                fields
+                    .iter()
+                    .map(|(name, field)| (Self::from(name.as_str()), field.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<&FieldDefinition> for Value {
+    fn from(field: &FieldDefinition) -> Self {
+        match &field.default {
+            Some(default) => default.clone(),
+            None => (&field.kind).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod path {
+        use crate::module::{AreaPath, Module, ModulePath};
+
+        #[test]
+        fn parse_paths() {
+            let path: ModulePath = "area_1/0/inner_area/2".parse().unwrap();
+            assert_eq!(path.index, 2);
+            assert_eq!(path.area().name(), "inner_area");
+            assert_eq!(path.to_string(), "area_1/0/inner_area/2");
+
+            let area: AreaPath = "area_1/0/inner_area".parse().unwrap();
+            assert_eq!(&area, path.area());
+
+            assert!("area_1".parse::<ModulePath>().is_err());
+            assert!("area_1/first".parse::<ModulePath>().is_err());
+            assert!("area_1/0".parse::<AreaPath>().is_err());
+            assert!("".parse::<AreaPath>().is_err());
+        }
+
+        #[test]
+        fn edit_tree() {
+            let mut page = Module::new("/pages/section");
+            page.insert(&"area_1/0".parse().unwrap(), Module::new("header"))
+                .unwrap();
+            page.insert(&"area_1/0/inner/0".parse().unwrap(), Module::new("image"))
+                .unwrap();
+            page.insert(&"area_1/0".parse().unwrap(), Module::new("section"))
+                .unwrap();
+
+            assert!(page
+                .insert(&"area_1/5".parse().unwrap(), Module::new("image"))
+                .is_err());
+            let nested = page.get(&"area_1/1/inner/0".parse().unwrap()).unwrap();
+            assert_eq!(nested.template, "image");
+
+            let removed = page.remove(&"area_1/0".parse().unwrap()).unwrap();
+            assert_eq!(removed.template, "section");
+            assert_eq!(page.areas["area_1"].len(), 1);
+            assert!(page.remove(&"area_1/1".parse().unwrap()).is_err());
+        }
+    }
+
+    mod value {
+        use crate::theme::{Field, FieldDefinition, PageRef};
+        use serde_yaml::Value;
+
+        #[test]
+        fn field_defaults() {
+            assert_eq!(Value::from(&Field::Markdown), Value::from(""));
+            assert_eq!(Value::from(&Field::Date), Value::from(""));
+            assert_eq!(
+                Value::from(&Field::PageRef(PageRef::default())),
+                Value::from("")
+            );
+            assert_eq!(Value::from(&Field::Image), Value::from(""));
+            assert_eq!(Value::from(&Field::Number), Value::from(0));
+            assert_eq!(Value::from(&Field::Boolean), Value::from(false));
+            assert_eq!(Value::from(&Field::Color), Value::from("#000000"));
+            let select = Field::Select(vec!["wide".to_string(), "narrow".to_string()]);
+            assert_eq!(Value::from(&select), Value::from("wide"));
+            assert_eq!(Value::from(&Field::Select(vec![])), Value::from(""));
+            assert_eq!(
+                Value::from(&Field::List(Default::default())),
+                Value::Sequence(vec![])
+            );
+
+            let definition: FieldDefinition =
+                serde_yaml::from_str("kind: number\ndefault: 3").unwrap();
+            assert_eq!(Value::from(&definition), Value::from(3));
         }
     }
 }
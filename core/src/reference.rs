@@ -0,0 +1,242 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::{
+    error::{FieldError, Result},
+    media::MediaLibrary,
+    module::Module,
+    store::{Page, Store},
+    theme::{Field, PageRef, Template, Theme},
+    INDEX_SLUG,
+};
+
+/// A field holding the id of a page or of a media
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Reference {
+    /// Id of the page holding the field
+    pub page: String,
+    pub name: String,
+    /// Path of the module holding the field, `None` for the page itself
+    pub module: Option<String>,
+    /// Path of the field, like `links.0.page` for a field of a list item
+    pub field: String,
+}
+
+/// Every reference must point to an existing page using one of the
+/// templates the field accepts, or to a media of the library
+pub(crate) fn reference_errors(
+    storage: &dyn Store,
+    media: Option<&MediaLibrary>,
+    template: &Template,
+    values: &HashMap<String, Value>,
+) -> Vec<FieldError> {
+    template
+        .held_ids(values)
+        .into_iter()
+        .filter_map(|held| {
+            let message = match held.kind {
+                Field::PageRef(page_ref) => match storage.get_page(held.id) {
+                    None => "page does not exist",
+                    Some(page) if !page_ref.accepts(&page.template) => {
+                        "page template is not accepted"
+                    }
+                    Some(..) => return None,
+                },
+                kind => match media.and_then(|media| media.get(held.id)) {
+                    None => "media does not exist",
+                    Some(media) if *kind == Field::Image && !media.is_image() => {
+                        "media is not an image"
+                    }
+                    Some(..) => return None,
+                },
+            };
+            Some(FieldError::new(&held.path, message))
+        })
+        .collect()
+}
+
+/// Put the published page or the media in place of every id a module tree
+/// holds, a page that is not online or a missing media resolves to null
+pub(crate) fn resolve_module(
+    storage: &dyn Store,
+    media: Option<&MediaLibrary>,
+    theme: &Theme,
+    now: DateTime<Utc>,
+    module: &mut Module,
+) {
+    if let Some(template) = theme.templates.get(&module.template) {
+        let resolve = |kind: &Field, id: &str| match kind {
+            Field::PageRef(page_ref) => resolve(storage, now, page_ref, id),
+            _ => media
+                .and_then(|media| media.get(id))
+                .and_then(|media| {
+                    let mut value = serde_yaml::to_value(&media).ok()?;
+                    value["url"] = media.url().into();
+                    Some(value)
+                })
+                .unwrap_or(Value::Null),
+        };
+        template.resolve_ids(&mut module.fields, &resolve);
+    }
+    for modules in module.areas.values_mut() {
+        for module in modules {
+            resolve_module(storage, media, theme, now, module);
+        }
+    }
+}
+
+/// What views get for a reference: `id`, `name`, `url` and the `fields`
+/// listed by the declaration
+fn resolve(storage: &dyn Store, now: DateTime<Utc>, page_ref: &PageRef, id: &str) -> Value {
+    let page = storage.get_page_info(id).filter(|page| page.in_window(now));
+    let module = storage
+        .get_published(id)
+        .filter(|module| page_ref.accepts(&module.template));
+    let (page, module) = match (page, module) {
+        (Some(page), Some(module)) => (page, module),
+        _ => return Value::Null,
+    };
+
+    let fields: Mapping = page_ref
+        .fields
+        .iter()
+        .filter_map(|name| Some((Value::from(name.as_str()), module.fields.get(name)?.clone())))
+        .collect();
+    let mut resolved = Mapping::new();
+    resolved.insert("id".into(), page.id.as_str().into());
+    resolved.insert("name".into(), page.name.as_str().into());
+    resolved.insert("url".into(), url(&page).into());
+    resolved.insert("fields".into(), Value::Mapping(fields));
+    Value::Mapping(resolved)
+}
+
+/// Url of a page once built, the index page is the root of the site
+fn url(page: &Page) -> String {
+    match page.path.as_str() {
+        INDEX_SLUG => "/".to_string(),
+        path => format!("/{}/", path),
+    }
+}
+
+/// An id held by a field of the draft or of the published version of a page
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Held {
+    id: String,
+    /// Held by a page reference, else by an image or a file field
+    page_ref: bool,
+    module: Option<String>,
+    field: String,
+}
+
+/// Ids held by the fields of every page, scanned on first use then kept up
+/// to date as pages are written, so finding what refers to a page or a media
+/// does not read every page again
+#[derive(Debug, Default)]
+pub(crate) struct ReferenceIndex {
+    pages: Option<HashMap<String, BTreeSet<Held>>>,
+}
+
+impl ReferenceIndex {
+    /// Scan every page again on next use, for a theme changing which
+    /// fields hold ids
+    pub(crate) fn clear(&mut self) {
+        self.pages = None;
+    }
+
+    /// Read again the draft and the published version of a page, once
+    /// either changed or the page is gone
+    pub(crate) fn update(&mut self, storage: &dyn Store, theme: &Theme, id: &str) {
+        if let Some(pages) = &mut self.pages {
+            let modules = [storage.get_page(id), storage.get_published(id)];
+            let held = held_by(theme, modules.iter().flatten());
+            match held.is_empty() {
+                true => pages.remove(id),
+                false => pages.insert(id.to_owned(), held),
+            };
+        }
+    }
+
+    /// Fields of drafts and published pages holding the id of a page, or of
+    /// a media when `media` is set
+    pub(crate) fn references_to(
+        &mut self,
+        storage: &dyn Store,
+        theme: &Theme,
+        id: &str,
+        media: bool,
+    ) -> Result<Vec<Reference>> {
+        let pages = match &self.pages {
+            Some(pages) => pages,
+            None => self.pages.insert(scan(storage, theme)?),
+        };
+        let mut references = vec![];
+        for (page_id, held) in pages {
+            let name = match storage.get_page_info(page_id) {
+                Some(page) => page.name,
+                None => continue,
+            };
+            references.extend(
+                held.iter()
+                    .filter(|held| held.id == id && held.page_ref != media)
+                    .map(|held| Reference {
+                        page: page_id.to_owned(),
+                        name: name.to_owned(),
+                        module: held.module.to_owned(),
+                        field: held.field.to_owned(),
+                    }),
+            );
+        }
+        references.sort();
+        Ok(references)
+    }
+}
+
+/// Ids held by every draft and published page, keyed by page id
+fn scan(storage: &dyn Store, theme: &Theme) -> Result<HashMap<String, BTreeSet<Held>>> {
+    let drafts = storage.get_pages()?;
+    let published = storage.get_published_pages()?;
+    let mut pages: HashMap<String, BTreeSet<Held>> = HashMap::new();
+    for (page_id, module) in drafts.iter().chain(published.iter()) {
+        let held = held_by(theme, [module].into_iter());
+        if !held.is_empty() {
+            pages.entry(page_id.to_owned()).or_default().extend(held);
+        }
+    }
+    Ok(pages)
+}
+
+fn held_by<'a>(theme: &Theme, modules: impl Iterator<Item = &'a Module>) -> BTreeSet<Held> {
+    let mut held = BTreeSet::new();
+    for module in modules {
+        find_refs(theme, module, None, &mut held);
+    }
+    held
+}
+
+fn find_refs(theme: &Theme, module: &Module, path: Option<String>, found: &mut BTreeSet<Held>) {
+    if let Some(template) = theme.templates.get(&module.template) {
+        found.extend(
+            template
+                .held_ids(&module.fields)
+                .into_iter()
+                .map(|held| Held {
+                    id: held.id.to_owned(),
+                    page_ref: matches!(held.kind, Field::PageRef(..)),
+                    module: path.clone(),
+                    field: held.path,
+                }),
+        );
+    }
+    for (area, modules) in &module.areas {
+        for (index, module) in modules.iter().enumerate() {
+            let path = match &path {
+                Some(path) => format!("{}/{}/{}", path, area, index),
+                None => format!("{}/{}", area, index),
+            };
+            find_refs(theme, module, Some(path), found);
+        }
+    }
+}
@@ -6,5 +6,5 @@ pub mod tera_renderer;
 
 pub trait Render: fmt::Debug + Send + Sync {
     fn load(&mut self, theme: &Theme) -> Result<()>;
-    fn render_module(&mut self, module: &Module) -> Result<String>;
+    fn render_module(&self, module: &Module) -> Result<String>;
 }
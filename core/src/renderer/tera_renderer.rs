@@ -1,13 +1,20 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use serde_yaml::Value;
 use tera::{self, Context};
 
-use crate::{error::Result, module::Module, renderer::Render, theme::Theme};
+use crate::{
+    error::Result,
+    module::Module,
+    renderer::Render,
+    theme::{Field, FieldDefinition, Theme},
+};
 
 #[derive(Debug)]
 pub struct TeraRenderer {
     tera: tera::Tera,
+    /// Fields of every template, to give views values fitting their kind
+    fields: HashMap<String, BTreeMap<String, FieldDefinition>>,
 }
 
 impl From<&Module> for tera::Context {
@@ -44,25 +51,91 @@ impl tera::Filter for AreaFilter {
     }
 }
 
+/// Html of a markdown text, raw html in the source is sanitized
+fn markdown_to_html(source: &str) -> String {
+    let options =
+        pulldown_cmark::Options::ENABLE_TABLES | pulldown_cmark::Options::ENABLE_STRIKETHROUGH;
+    let parser = pulldown_cmark::Parser::new_ext(source, options);
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, parser);
+    ammonia::clean(&html)
+}
+
+/// Value of a field as views see it, markdown is turned into html, lists
+/// and objects keep the kinds of their fields
+fn view_value(kind: &Field, value: &Value) -> tera::Value {
+    match (kind, value) {
+        (Field::Markdown, Value::String(source)) => tera::Value::String(markdown_to_html(source)),
+        (Field::List(fields), Value::Sequence(items)) => items
+            .iter()
+            .map(|item| object_value(fields, item))
+            .collect(),
+        (Field::Object(fields), value) => object_value(fields, value),
+        (_, value) => tera::to_value(value).unwrap_or_default(),
+    }
+}
+
+fn object_value(fields: &BTreeMap<String, FieldDefinition>, value: &Value) -> tera::Value {
+    match value {
+        Value::Mapping(mapping) => mapping
+            .iter()
+            .filter_map(|(name, value)| {
+                let name = name.as_str()?;
+                let value = match fields.get(name) {
+                    Some(field) => view_value(&field.kind, value),
+                    None => tera::to_value(value).unwrap_or_default(),
+                };
+                Some((name.to_string(), value))
+            })
+            .collect::<tera::Map<_, _>>()
+            .into(),
+        value => tera::to_value(value).unwrap_or_default(),
+    }
+}
+
+fn new_tera() -> tera::Tera {
+    let mut tera: tera::Tera = Default::default();
+    tera.register_filter("area", AreaFilter);
+    tera
+}
+
 impl Default for TeraRenderer {
     fn default() -> Self {
-        let mut tera: tera::Tera = Default::default();
-        tera.register_filter("area", AreaFilter);
-        Self { tera }
+        Self {
+            tera: new_tera(),
+            fields: HashMap::new(),
+        }
     }
 }
 
 impl Render for TeraRenderer {
+    /// Compile the view of every template, as read with the theme, in a new
+    /// tera instance, the current one is only replaced once all of them are
+    /// loaded
     fn load(&mut self, theme: &Theme) -> Result<()> {
-        for (name, page) in &theme.templates {
-            self.tera.add_template_file(&page.view, Some(name))?;
-        }
+        let mut tera = new_tera();
+        let sources = theme
+            .templates
+            .iter()
+            .map(|(name, template)| (name.as_str(), template.source.as_str()));
+        tera.add_raw_templates(sources)?;
+        self.tera = tera;
+        self.fields = theme
+            .templates
+            .iter()
+            .map(|(name, template)| (name.to_owned(), template.fields.clone()))
+            .collect();
 
         Ok(())
     }
 
-    fn render_module(&mut self, module: &Module) -> Result<String> {
+    fn render_module(&self, module: &Module) -> Result<String> {
         let mut context = Context::from(module);
+        for (name, field) in self.fields.get(&module.template).into_iter().flatten() {
+            if let Some(value) = module.fields.get(name) {
+                context.insert(name, &view_value(&field.kind, value));
+            }
+        }
         for (name, modules) in &module.areas {
             let mut area_html = String::new();
             for module in modules {
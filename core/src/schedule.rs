@@ -0,0 +1,65 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::store::Page;
+
+/// Source of the current time, tests swap it to move through a schedule
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The real time of the machine
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleAction {
+    Publish,
+    Unpublish,
+}
+
+/// A publication change planned for a page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScheduledChange {
+    pub id: String,
+    pub name: String,
+    pub action: ScheduleAction,
+    pub at: DateTime<Utc>,
+}
+
+impl ScheduledChange {
+    /// Changes planned for a page, in the order they happen
+    pub(crate) fn of_page(page: &Page) -> Vec<Self> {
+        let planned = [
+            (ScheduleAction::Publish, page.publish_at),
+            (ScheduleAction::Unpublish, page.unpublish_at),
+        ];
+        planned
+            .into_iter()
+            .filter_map(|(action, at)| {
+                at.map(|at| Self {
+                    id: page.id.to_owned(),
+                    name: page.name.to_owned(),
+                    action,
+                    at,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Page {
+    /// Whether the time is inside the publication window of the page
+    pub fn in_window(&self, now: DateTime<Utc>) -> bool {
+        self.publish_at.is_none_or(|at| at <= now) && self.unpublish_at.is_none_or(|at| now < at)
+    }
+}
@@ -1,22 +1,143 @@
 use std::{collections::HashMap, fmt};
 
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use crate::{error::Result, module::Module};
+use crate::{access::AccessEntry, audit::AuditEntry, error::Result, module::Module};
 
+pub(crate) mod slug;
 pub mod yaml_storage;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Page {
     pub id: String,
     pub name: String,
+    /// Unique part of the url identifying the page
+    pub slug: String,
+    pub parent: Option<String>,
+    /// Position of the page among the children of its parent
+    pub order: usize,
+    /// Url of the page made of its ancestors slugs, `blog/post-1`
+    pub path: String,
+    pub status: PageStatus,
+    /// When the page goes live, it is hidden until then
+    pub publish_at: Option<DateTime<Utc>>,
+    /// When the page comes down
+    pub unpublish_at: Option<DateTime<Utc>>,
+    /// Rights given on this page and the pages under it
+    pub access: Vec<AccessEntry>,
+}
+
+/// Whether the public site shows a page, and if its draft was changed since
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PageStatus {
+    /// Never published, or unpublished since
+    #[default]
+    Draft,
+    /// The published snapshot is the same as the draft
+    Published,
+    /// Published, but the draft was changed after that
+    Modified,
+}
+
+/// A version of a page recorded when it was saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    /// Starts at 1 and grows with every save of the page
+    pub number: usize,
+    pub date: DateTime<Utc>,
+    pub author: Option<String>,
+    pub module: Module,
+}
+
+/// A page with the pages placed under it
+#[derive(Debug, Serialize)]
+pub struct PageNode {
+    #[serde(flatten)]
+    pub page: Page,
+    pub children: Vec<PageNode>,
+}
+
+impl PageNode {
+    /// Nest the pages under their parents, a page whose parent is missing
+    /// ends up at the root
+    pub(crate) fn tree(pages: Vec<Page>) -> Vec<PageNode> {
+        let ids: Vec<String> = pages.iter().map(|page| page.id.to_owned()).collect();
+        let mut children: HashMap<Option<String>, Vec<Page>> = HashMap::new();
+        for page in pages {
+            let parent = page.parent.to_owned().filter(|parent| ids.contains(parent));
+            children.entry(parent).or_default().push(page);
+        }
+        Self::nodes(&mut children, None)
+    }
+
+    fn nodes(
+        children: &mut HashMap<Option<String>, Vec<Page>>,
+        parent: Option<String>,
+    ) -> Vec<PageNode> {
+        let mut pages = children.remove(&parent).unwrap_or_default();
+        pages.sort_by(|a, b| (a.order, &a.id).cmp(&(b.order, &b.id)));
+        pages
+            .into_iter()
+            .map(|page| {
+                let children = Self::nodes(children, Some(page.id.to_owned()));
+                PageNode { page, children }
+            })
+            .collect()
+    }
 }
 
 pub trait Store: fmt::Debug + Send + Sync {
     fn summary(&self) -> Vec<Page>;
+    /// Every page module, keyed by page id
     fn get_pages(&self) -> Result<HashMap<String, Module>>;
+    /// Working draft of a page
+    fn get_page(&self, id: &str) -> Option<Module>;
+    /// Snapshot of a page taken when it was last published
+    fn get_published(&self, id: &str) -> Option<Module>;
+    /// Every published snapshot, keyed by page id
+    fn get_published_pages(&self) -> Result<HashMap<String, Module>>;
     fn get_page_by_name(&self, name: &str) -> Option<Module>;
+    fn get_page_info(&self, id: &str) -> Option<Page>;
+    fn get_page_by_slug(&self, slug: &str) -> Option<Page>;
     fn page_exists(&self, id: &str) -> bool;
-    fn create_page(&mut self, name: &str, module: Module) -> Result<String>;
+    /// Every version of a page, oldest first
+    fn get_revisions(&self, id: &str) -> Result<Vec<Revision>>;
+    fn create_page(
+        &mut self,
+        name: &str,
+        slug: &str,
+        module: Module,
+        author: Option<&str>,
+        date: DateTime<Utc>,
+    ) -> Result<String>;
+    /// Save the draft of a page and record it as a new revision made at
+    /// `date`
+    fn update_page(
+        &mut self,
+        id: &str,
+        module: &Module,
+        author: Option<&str>,
+        date: DateTime<Utc>,
+    ) -> Result<()>;
+    fn update_slug(&mut self, id: &str, slug: &str) -> Result<()>;
+    /// Put the page under `parent`, or at the root, at `position` among its
+    /// new siblings
+    fn move_page(&mut self, id: &str, parent: Option<&str>, position: usize) -> Result<()>;
+    /// Replace the published snapshot of a page with its draft
+    fn publish_page(&mut self, id: &str) -> Result<()>;
+    fn unpublish_page(&mut self, id: &str) -> Result<()>;
+    fn schedule_page(
+        &mut self,
+        id: &str,
+        publish_at: Option<DateTime<Utc>>,
+        unpublish_at: Option<DateTime<Utc>>,
+    ) -> Result<()>;
+    fn set_access(&mut self, id: &str, access: Vec<AccessEntry>) -> Result<()>;
     fn delete_page(&mut self, name: &str) -> Result<Module>;
+    /// Add an entry at the end of the audit log
+    fn append_audit(&mut self, entry: &AuditEntry) -> Result<()>;
+    /// Every entry of the audit log, oldest first
+    fn get_audit(&self) -> Result<Vec<AuditEntry>>;
 }
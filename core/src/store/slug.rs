@@ -0,0 +1,79 @@
+/// Turn a page name into something usable in an url, accented letters are
+/// replaced by their plain version
+pub(crate) fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if let Some(plain) = transliterate(c) {
+            slug.push_str(plain);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    slug.trim_end_matches('-').to_string()
+}
+
+/// A slug only has lowercase letters, digits and single dashes between them
+pub(crate) fn is_valid_slug(slug: &str) -> bool {
+    !slug.is_empty()
+        && slug.split('-').all(|part| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        })
+}
+
+fn transliterate(c: char) -> Option<&'static str> {
+    let plain = match c {
+        'à' | 'â' | 'ä' | 'á' | 'ã' | 'å' => "a",
+        'ç' => "c",
+        'é' | 'è' | 'ê' | 'ë' => "e",
+        'î' | 'ï' | 'í' | 'ì' => "i",
+        'ñ' => "n",
+        'ô' | 'ö' | 'ó' | 'ò' | 'õ' => "o",
+        'ù' | 'û' | 'ü' | 'ú' => "u",
+        'ÿ' | 'ý' => "y",
+        'œ' => "oe",
+        'æ' => "ae",
+        'ß' => "ss",
+        _ => return None,
+    };
+    Some(plain)
+}
+
+#[cfg(test)]
+mod tests {
+    mod slugify {
+        use crate::store::slug::{is_valid_slug, slugify};
+
+        #[test]
+        fn slugify_names() {
+            assert_eq!(slugify("first test"), "first-test");
+            assert_eq!(slugify("  Hello, World!  "), "hello-world");
+            assert_eq!(slugify("--"), "");
+        }
+
+        #[test]
+        fn transliterate_french() {
+            assert_eq!(slugify("Élève à l'école"), "eleve-a-l-ecole");
+            assert_eq!(
+                slugify("Cœur de Noël, ça déçoit"),
+                "coeur-de-noel-ca-decoit"
+            );
+            assert_eq!(slugify("Où êtes-vous ?"), "ou-etes-vous");
+        }
+
+        #[test]
+        fn validate_slugs() {
+            assert!(is_valid_slug("first-test-2"));
+            assert!(!is_valid_slug(""));
+            assert!(!is_valid_slug("First"));
+            assert!(!is_valid_slug("first--test"));
+            assert!(!is_valid_slug("-first"));
+            assert!(!is_valid_slug("first/test"));
+        }
+    }
+}
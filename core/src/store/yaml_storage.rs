@@ -1,15 +1,29 @@
-use std::{collections::HashMap, fs, path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Write,
+    path,
+};
 
+use chrono::{DateTime, Utc};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    access::AccessEntry,
+    audit::AuditEntry,
     error::{Error, Result},
     module::Module,
-    store::Store,
+    store::{
+        slug::{is_valid_slug, slugify},
+        Store,
+    },
 };
 
-use super::Page;
+use super::{Page, PageStatus, Revision};
+
+/// Name of the audit log, next to the storage file
+const AUDIT_FILE: &str = "audit.yml";
 
 /// YamlStorage is able to save and load a yaml file as a storage
 #[derive(Debug)]
@@ -23,11 +37,26 @@ impl TryFrom<&str> for YamlStorage {
 
     fn try_from(value: &str) -> Result<Self> {
         let file = fs::File::open(value)?;
-        let storage = serde_yaml::from_reader(file)?;
-        Ok(Self {
+        let stored: StoredFile = serde_yaml::from_reader(file)?;
+        let (mut storage, converted) = YamlStorageFile::from_stored(stored);
+        if let Some(parent) = path::Path::new(value).parent() {
+            storage.base = parent.to_path_buf();
+        }
+        // Pages were online as soon as they existed before publishing came
+        for id in &converted {
+            let published = storage.get_published_file(id);
+            if !published.is_file() {
+                fs::copy(storage.get_file(id), published)?;
+            }
+        }
+        let storage = Self {
             yaml_file: path::PathBuf::from(value),
             storage,
-        })
+        };
+        if !converted.is_empty() {
+            storage.persist_storage()?;
+        }
+        Ok(storage)
     }
 }
 
@@ -51,20 +80,105 @@ impl Store for YamlStorage {
         self.storage.get_pages()
     }
 
+    fn get_page(&self, id: &str) -> Option<Module> {
+        self.storage.get_page(id)
+    }
+
+    fn get_published(&self, id: &str) -> Option<Module> {
+        self.storage.get_published(id)
+    }
+
+    fn get_published_pages(&self) -> Result<HashMap<String, Module>> {
+        self.storage.get_published_pages()
+    }
+
     fn get_page_by_name(&self, name: &str) -> Option<Module> {
         self.storage.get_page_by_name(name)
     }
 
+    fn get_page_info(&self, id: &str) -> Option<Page> {
+        self.storage.get_page_info(id)
+    }
+
+    fn get_page_by_slug(&self, slug: &str) -> Option<Page> {
+        self.storage.get_page_by_slug(slug)
+    }
+
     fn page_exists(&self, id: &str) -> bool {
         self.storage.page_exists(id)
     }
 
-    fn create_page(&mut self, name: &str, module: Module) -> Result<String> {
-        let id = self.storage.create_page(name, module)?;
+    fn get_revisions(&self, id: &str) -> Result<Vec<Revision>> {
+        self.storage.get_revisions(id)
+    }
+
+    fn create_page(
+        &mut self,
+        name: &str,
+        slug: &str,
+        module: Module,
+        author: Option<&str>,
+        date: DateTime<Utc>,
+    ) -> Result<String> {
+        let id = self.storage.create_page(name, slug, module, author, date)?;
         self.persist_storage()?;
         Ok(id)
     }
 
+    fn update_page(
+        &mut self,
+        id: &str,
+        module: &Module,
+        author: Option<&str>,
+        date: DateTime<Utc>,
+    ) -> Result<()> {
+        self.storage.update_page(id, module, author, date)?;
+        self.persist_storage()
+    }
+
+    fn update_slug(&mut self, id: &str, slug: &str) -> Result<()> {
+        self.storage.update_slug(id, slug)?;
+        self.persist_storage()
+    }
+
+    fn move_page(&mut self, id: &str, parent: Option<&str>, position: usize) -> Result<()> {
+        self.storage.move_page(id, parent, position)?;
+        self.persist_storage()
+    }
+
+    fn publish_page(&mut self, id: &str) -> Result<()> {
+        self.storage.publish_page(id)?;
+        self.persist_storage()
+    }
+
+    fn unpublish_page(&mut self, id: &str) -> Result<()> {
+        self.storage.unpublish_page(id)?;
+        self.persist_storage()
+    }
+
+    fn schedule_page(
+        &mut self,
+        id: &str,
+        publish_at: Option<DateTime<Utc>>,
+        unpublish_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        self.storage.schedule_page(id, publish_at, unpublish_at)?;
+        self.persist_storage()
+    }
+
+    fn set_access(&mut self, id: &str, access: Vec<AccessEntry>) -> Result<()> {
+        self.storage.set_access(id, access)?;
+        self.persist_storage()
+    }
+
+    fn append_audit(&mut self, entry: &AuditEntry) -> Result<()> {
+        self.storage.append_audit(entry)
+    }
+
+    fn get_audit(&self) -> Result<Vec<AuditEntry>> {
+        self.storage.get_audit()
+    }
+
     fn delete_page(&mut self, id: &str) -> Result<Module> {
         let module = self.storage.delete_page(id)?;
         self.persist_storage()?;
@@ -72,13 +186,122 @@ impl Store for YamlStorage {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Serialize)]
 struct YamlStorageFile {
+    /// Folder of the storage file, holding the audit log
+    #[serde(skip)]
+    base: path::PathBuf,
+    folder: path::PathBuf,
+    pages: HashMap<String, PageEntry>,
+}
+
+/// What the storage file knows about a page, its module lives in its own file
+#[derive(Debug, Deserialize, Serialize)]
+struct PageEntry {
+    name: String,
+    slug: String,
+    #[serde(default)]
+    parent: Option<String>,
+    #[serde(default)]
+    order: usize,
+    #[serde(default)]
+    status: PageStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    publish_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    unpublish_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    access: Vec<AccessEntry>,
+}
+
+/// The storage file as written by any version, converted once loaded
+#[derive(Deserialize)]
+struct StoredFile {
     folder: path::PathBuf,
-    pages: HashMap<String, String>,
+    pages: HashMap<String, StoredPage>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredPage {
+    Entry(StoredEntry),
+    /// Written before pages had a slug, only their name
+    Name(String),
+}
+
+#[derive(Deserialize)]
+struct StoredEntry {
+    /// Missing when written before pages could be published
+    status: Option<PageStatus>,
+    #[serde(flatten)]
+    entry: PageEntry,
 }
 
 impl YamlStorageFile {
+    /// Give the pages stored by name only a unique slug made from it, and
+    /// the pages stored without a status the published one. Returns the ids
+    /// of the converted pages, they still need their published snapshot.
+    fn from_stored(stored: StoredFile) -> (Self, Vec<String>) {
+        let mut pages = HashMap::new();
+        let mut names = vec![];
+        let mut converted = vec![];
+        for (id, page) in stored.pages {
+            match page {
+                StoredPage::Entry(StoredEntry {
+                    status: Some(status),
+                    mut entry,
+                }) => {
+                    entry.status = status;
+                    pages.insert(id, entry);
+                }
+                StoredPage::Entry(StoredEntry {
+                    status: None,
+                    mut entry,
+                }) => {
+                    entry.status = PageStatus::Published;
+                    converted.push(id.to_owned());
+                    pages.insert(id, entry);
+                }
+                StoredPage::Name(name) => names.push((name, id)),
+            }
+        }
+
+        names.sort();
+        let mut slugs: HashSet<String> = pages.values().map(|e| e.slug.to_owned()).collect();
+        for (order, (name, id)) in names.into_iter().enumerate() {
+            let mut base = slugify(&name);
+            if !is_valid_slug(&base) {
+                base = id.to_lowercase();
+            }
+            let mut slug = base.to_owned();
+            let mut count = 1;
+            while slugs.contains(&slug) {
+                count += 1;
+                slug = format!("{}-{}", base, count);
+            }
+            slugs.insert(slug.to_owned());
+            converted.push(id.to_owned());
+            let entry = PageEntry {
+                name,
+                slug,
+                parent: None,
+                order,
+                status: PageStatus::Published,
+                publish_at: None,
+                unpublish_at: None,
+                access: vec![],
+            };
+            pages.insert(id, entry);
+        }
+
+        let storage = Self {
+            base: path::PathBuf::new(),
+            folder: stored.folder,
+            pages,
+        };
+        (storage, converted)
+    }
+
     fn get_uid<T>(&self, generator: &mut T) -> String
     where
         T: IdGenerator,
@@ -96,67 +319,314 @@ impl YamlStorageFile {
         self.folder.join(format!("{}.yml", &id))
     }
 
-    fn contains_name(&self, page_name: &str) -> Option<(&String, &String)> {
-        self.pages.iter().find(|p| p.1 == page_name)
+    fn get_published_file(&self, id: &str) -> path::PathBuf {
+        self.folder.join(format!("{}.published.yml", &id))
     }
-}
 
-impl From<(&String, &String)> for Page {
-    fn from(page: (&String, &String)) -> Self {
-        Self {
-            id: page.0.to_owned(),
-            name: page.1.to_owned(),
+    fn get_revisions_file(&self, id: &str) -> path::PathBuf {
+        self.folder.join(format!("{}.revisions.yml", &id))
+    }
+
+    /// Audit log of the site, one yaml document per entry
+    fn get_audit_file(&self) -> path::PathBuf {
+        self.base.join(AUDIT_FILE)
+    }
+
+    /// Append the module to the revisions of the page
+    fn record_revision(
+        &self,
+        id: &str,
+        module: &Module,
+        author: Option<&str>,
+        date: DateTime<Utc>,
+    ) -> Result<()> {
+        let mut revisions = self.get_revisions(id)?;
+        revisions.push(Revision {
+            number: revisions.last().map_or(1, |revision| revision.number + 1),
+            date,
+            author: author.map(str::to_owned),
+            module: module.clone(),
+        });
+        let file = fs::File::create(self.get_revisions_file(id))?;
+        serde_yaml::to_writer(file, &revisions)?;
+        Ok(())
+    }
+
+    fn read_module(path: &path::Path) -> Result<Module> {
+        let file = fs::File::open(path)?;
+        Ok(serde_yaml::from_reader(file)?)
+    }
+
+    fn contains_name(&self, page_name: &str) -> Option<(&String, &PageEntry)> {
+        self.pages.iter().find(|p| p.1.name == page_name)
+    }
+
+    /// Slugs of the page and its ancestors joined from the root
+    fn url_path(&self, id: &str) -> String {
+        let mut slugs = vec![];
+        let mut current = self.pages.get(id);
+        while let Some(entry) = current {
+            // A broken storage file could hold a cycle
+            if slugs.len() > self.pages.len() {
+                break;
+            }
+            slugs.push(entry.slug.as_str());
+            current = entry
+                .parent
+                .as_ref()
+                .and_then(|parent| self.pages.get(parent));
         }
+        slugs.reverse();
+        slugs.join("/")
+    }
+
+    fn to_page(&self, id: &str, entry: &PageEntry) -> Page {
+        Page {
+            id: id.to_owned(),
+            name: entry.name.to_owned(),
+            slug: entry.slug.to_owned(),
+            parent: entry.parent.to_owned(),
+            order: entry.order,
+            path: self.url_path(id),
+            status: entry.status,
+            publish_at: entry.publish_at,
+            unpublish_at: entry.unpublish_at,
+            access: entry.access.to_owned(),
+        }
+    }
+
+    /// Ids of the pages under `parent`, sorted by their order
+    fn children(&self, parent: Option<&str>) -> Vec<String> {
+        let mut children: Vec<(&String, &PageEntry)> = self
+            .pages
+            .iter()
+            .filter(|(_, entry)| entry.parent.as_deref() == parent)
+            .collect();
+        children.sort_by_key(|(id, entry)| (entry.order, id.to_owned()));
+        children.into_iter().map(|(id, _)| id.to_owned()).collect()
     }
 }
 
 impl Store for YamlStorageFile {
     fn summary(&self) -> Vec<Page> {
-        self.pages.iter().map(|p| p.into()).collect()
+        self.pages
+            .iter()
+            .map(|(id, entry)| self.to_page(id, entry))
+            .collect()
     }
 
     fn get_pages(&self) -> Result<HashMap<String, Module>> {
         let mut pages = HashMap::new();
-        for (id, name) in &self.pages {
-            let file = fs::File::open(self.folder.join(id))?;
+        for id in self.pages.keys() {
+            let file = fs::File::open(self.get_file(id))?;
             let template: Module = serde_yaml::from_reader(file)?;
-            pages.insert(name.to_owned(), template);
+            pages.insert(id.to_owned(), template);
         }
 
         Ok(pages)
     }
 
-    fn get_page_by_name(&self, name: &str) -> Option<Module> {
-        if let Some(page) = self.contains_name(name) {
-            let file = fs::File::open(self.get_file(page.0)).ok()?;
+    fn get_page(&self, id: &str) -> Option<Module> {
+        if self.page_exists(id) {
+            let file = fs::File::open(self.get_file(id)).ok()?;
             Some(serde_yaml::from_reader(file).ok()?)
         } else {
             None
         }
     }
 
+    fn get_published(&self, id: &str) -> Option<Module> {
+        match self.pages.get(id)?.status {
+            PageStatus::Draft => None,
+            _ => Self::read_module(&self.get_published_file(id)).ok(),
+        }
+    }
+
+    fn get_published_pages(&self) -> Result<HashMap<String, Module>> {
+        let mut pages = HashMap::new();
+        for (id, entry) in &self.pages {
+            if entry.status != PageStatus::Draft {
+                let module = Self::read_module(&self.get_published_file(id))?;
+                pages.insert(id.to_owned(), module);
+            }
+        }
+
+        Ok(pages)
+    }
+
+    fn get_page_by_name(&self, name: &str) -> Option<Module> {
+        let (id, _) = self.contains_name(name)?;
+        self.get_page(id)
+    }
+
+    fn get_page_info(&self, id: &str) -> Option<Page> {
+        self.pages.get(id).map(|entry| self.to_page(id, entry))
+    }
+
+    fn get_page_by_slug(&self, slug: &str) -> Option<Page> {
+        self.pages
+            .iter()
+            .find(|p| p.1.slug == slug)
+            .map(|(id, entry)| self.to_page(id, entry))
+    }
+
     fn page_exists(&self, id: &str) -> bool {
         self.pages.contains_key(id)
     }
 
-    fn create_page(&mut self, name: &str, module: Module) -> Result<String> {
+    fn get_revisions(&self, id: &str) -> Result<Vec<Revision>> {
+        match fs::File::open(self.get_revisions_file(id)) {
+            Ok(file) => Ok(serde_yaml::from_reader(file)?),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn create_page(
+        &mut self,
+        name: &str,
+        slug: &str,
+        module: Module,
+        author: Option<&str>,
+        date: DateTime<Utc>,
+    ) -> Result<String> {
         let id = self.get_uid(&mut Random::default());
         let file = fs::File::create(self.get_file(&id))?;
         serde_yaml::to_writer(file, &module)?;
-        self.pages.insert(id.to_owned(), name.to_owned());
+        self.record_revision(&id, &module, author, date)?;
+        let entry = PageEntry {
+            name: name.to_owned(),
+            slug: slug.to_owned(),
+            parent: None,
+            order: self.children(None).len(),
+            status: PageStatus::Draft,
+            publish_at: None,
+            unpublish_at: None,
+            access: vec![],
+        };
+        self.pages.insert(id.to_owned(), entry);
         Ok(id)
     }
 
+    fn update_page(
+        &mut self,
+        id: &str,
+        module: &Module,
+        author: Option<&str>,
+        date: DateTime<Utc>,
+    ) -> Result<()> {
+        if !self.page_exists(id) {
+            return Err(Error::PageNotFound);
+        }
+        let file = fs::File::create(self.get_file(id))?;
+        serde_yaml::to_writer(file, module)?;
+        self.record_revision(id, module, author, date)?;
+        let entry = self.pages.get_mut(id).ok_or(Error::PageNotFound)?;
+        if entry.status == PageStatus::Published {
+            entry.status = PageStatus::Modified;
+        }
+        Ok(())
+    }
+
+    fn update_slug(&mut self, id: &str, slug: &str) -> Result<()> {
+        let entry = self.pages.get_mut(id).ok_or(Error::PageNotFound)?;
+        entry.slug = slug.to_owned();
+        Ok(())
+    }
+
+    fn move_page(&mut self, id: &str, parent: Option<&str>, position: usize) -> Result<()> {
+        let entry = self.pages.get_mut(id).ok_or(Error::PageNotFound)?;
+        let previous_parent = entry.parent.take();
+        entry.parent = parent.map(str::to_owned);
+
+        let mut previous_siblings = self.children(previous_parent.as_deref());
+        previous_siblings.retain(|sibling| sibling != id);
+        let mut siblings = self.children(parent);
+        siblings.retain(|sibling| sibling != id);
+        siblings.insert(position.min(siblings.len()), id.to_owned());
+
+        // The new siblings come last so they win when the parent is unchanged
+        for ids in [previous_siblings, siblings] {
+            for (order, sibling) in ids.iter().enumerate() {
+                if let Some(entry) = self.pages.get_mut(sibling) {
+                    entry.order = order;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn publish_page(&mut self, id: &str) -> Result<()> {
+        let draft = self.get_file(id);
+        let published = self.get_published_file(id);
+        let entry = self.pages.get_mut(id).ok_or(Error::PageNotFound)?;
+        fs::copy(draft, published)?;
+        entry.status = PageStatus::Published;
+        Ok(())
+    }
+
+    fn unpublish_page(&mut self, id: &str) -> Result<()> {
+        let published = self.get_published_file(id);
+        let entry = self.pages.get_mut(id).ok_or(Error::PageNotFound)?;
+        if published.is_file() {
+            fs::remove_file(published)?;
+        }
+        entry.status = PageStatus::Draft;
+        Ok(())
+    }
+
+    fn schedule_page(
+        &mut self,
+        id: &str,
+        publish_at: Option<DateTime<Utc>>,
+        unpublish_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let entry = self.pages.get_mut(id).ok_or(Error::PageNotFound)?;
+        entry.publish_at = publish_at;
+        entry.unpublish_at = unpublish_at;
+        Ok(())
+    }
+
+    fn set_access(&mut self, id: &str, access: Vec<AccessEntry>) -> Result<()> {
+        let entry = self.pages.get_mut(id).ok_or(Error::PageNotFound)?;
+        entry.access = access;
+        Ok(())
+    }
+
     fn delete_page(&mut self, id: &str) -> Result<Module> {
         let path = self.get_file(id);
         let file = fs::File::open(&path)?;
         let module = serde_yaml::from_reader(file)?;
         fs::remove_file(path)?;
+        for path in [self.get_published_file(id), self.get_revisions_file(id)] {
+            if path.is_file() {
+                fs::remove_file(path)?;
+            }
+        }
         self.pages.remove(id);
-        dbg!(&self.pages);
 
         Ok(module)
     }
+
+    fn append_audit(&mut self, entry: &AuditEntry) -> Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.get_audit_file())?;
+        file.write_all(serde_yaml::to_string(entry)?.as_bytes())?;
+        Ok(())
+    }
+
+    fn get_audit(&self) -> Result<Vec<AuditEntry>> {
+        let content = match fs::read_to_string(self.get_audit_file()) {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(error) => return Err(error.into()),
+        };
+        serde_yaml::Deserializer::from_str(&content)
+            .map(|document| Ok(AuditEntry::deserialize(document)?))
+            .collect()
+    }
 }
 
 trait IdGenerator {
@@ -195,6 +665,7 @@ mod tests {
         #[test]
         fn generate_ids() {
             let mut storage = YamlStorageFile {
+                base: "/".into(),
                 folder: "/home".into(),
                 pages: HashMap::new(),
             };
@@ -203,7 +674,17 @@ mod tests {
 
             assert_eq!(&id, "1");
 
-            storage.pages.insert(id, "first".to_string());
+            let entry = PageEntry {
+                name: "first".to_string(),
+                slug: "first".to_string(),
+                parent: None,
+                order: 0,
+                status: PageStatus::Draft,
+                publish_at: None,
+                unpublish_at: None,
+                access: vec![],
+            };
+            storage.pages.insert(id, entry);
             generator.count = 0;
 
             let id = storage.get_uid(&mut generator);
@@ -0,0 +1,372 @@
+use std::{
+    collections::BTreeSet,
+    error::Error as StdError,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use tera::ast::{Expr, ExprVal, Node};
+
+use crate::{
+    error::Error,
+    theme::{Area, Field, FieldDefinition},
+};
+
+use super::{is_page, names_template, Template, Theme};
+
+/// A problem found in a theme file
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub message: String,
+    pub severity: Severity,
+}
+
+/// Whether a problem stops the theme from loading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Error,
+    /// Reported by the theme check, the theme still loads
+    Warning,
+}
+
+impl Diagnostic {
+    fn new(path: &Path, line: Option<usize>, message: String) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            line,
+            message,
+            severity: Severity::Error,
+        }
+    }
+
+    fn warning(path: &Path, line: Option<usize>, message: String) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::new(path, line, message)
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub(crate) fn from_error(path: &Path, error: Error) -> Self {
+        match error {
+            Error::Serde(error) => {
+                let line = error.location().map(|location| location.line());
+                Self::new(path, line, error.to_string())
+            }
+            error => Self::new(path, None, error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: ", self.path.display(), line)?,
+            None => write!(f, "{}: ", self.path.display())?,
+        }
+        if self.severity == Severity::Warning {
+            write!(f, "warning: ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Look for problems spanning several files of a parsed theme
+pub(crate) fn check_theme(theme: &Theme, base_path: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for (name, template) in &theme.templates {
+        let index = Path::new(base_path)
+            .join(name.trim_start_matches('/'))
+            .join("index.yaml");
+        diagnostics.extend(check_fields(theme, template, &index));
+        diagnostics.extend(check_areas(theme, template, &index));
+        diagnostics.extend(check_view(template, &index));
+    }
+    diagnostics
+}
+
+/// A select field must offer at least one option, a page reference must
+/// only accept existing page templates and a default value must follow the
+/// rules of its field
+fn check_fields(theme: &Theme, template: &Template, index: &Path) -> Vec<Diagnostic> {
+    let source = fs::read_to_string(index).unwrap_or_default();
+    let mut diagnostics = vec![];
+    for (name, field) in &template.fields {
+        for (path, nested) in nested_fields(name, field) {
+            let line = find_line(&source, path.rsplit('.').next().unwrap_or(&path));
+            match &nested.kind {
+                Field::Select(options) if options.is_empty() => diagnostics.push(Diagnostic::new(
+                    index,
+                    line,
+                    format!("select field {} has no option", path),
+                )),
+                Field::PageRef(page_ref) => {
+                    for accepted in &page_ref.templates {
+                        let exists = theme
+                            .templates
+                            .keys()
+                            .any(|name| is_page(name) && names_template(accepted, name));
+                        if !exists {
+                            diagnostics.push(Diagnostic::new(
+                                index,
+                                find_line(&source, accepted),
+                                format!(
+                                    "field {} references unknown page template {}",
+                                    path, accepted
+                                ),
+                            ));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let errors = field
+            .default
+            .as_ref()
+            .map(|value| field.validate(name, value, true))
+            .unwrap_or_default();
+        for error in errors {
+            diagnostics.push(Diagnostic::new(
+                index,
+                find_line(&source, name),
+                format!(
+                    "default value of field {} is invalid: {}",
+                    error.field, error.message
+                ),
+            ));
+        }
+    }
+    diagnostics
+}
+
+/// A field and every field nested in its lists and objects, with their paths
+fn nested_fields<'a>(path: &str, field: &'a FieldDefinition) -> Vec<(String, &'a FieldDefinition)> {
+    let mut fields = vec![(path.to_string(), field)];
+    if let Field::List(nested) | Field::Object(nested) = &field.kind {
+        for (name, field) in nested {
+            fields.extend(nested_fields(&format!("{}.{}", path, name), field));
+        }
+    }
+    fields
+}
+
+/// Every component listed by an area must exist in the theme
+fn check_areas(theme: &Theme, template: &Template, index: &Path) -> Vec<Diagnostic> {
+    let source = fs::read_to_string(index).unwrap_or_default();
+    let mut diagnostics = vec![];
+    for (area_name, area) in &template.areas {
+        let components = match area {
+            Area::All => continue,
+            Area::With(components) | Area::Without(components) => components,
+        };
+        for component in components {
+            let exists = theme
+                .templates
+                .keys()
+                .any(|name| !is_page(name) && names_template(component, name));
+            if !exists {
+                diagnostics.push(Diagnostic::new(
+                    index,
+                    find_line(&source, component),
+                    format!(
+                        "area {} references unknown component {}",
+                        area_name, component
+                    ),
+                ));
+            }
+        }
+    }
+    diagnostics
+}
+
+/// The view must exist and be a valid tera template. Using a variable that
+/// is neither a field nor an area is only a warning, it renders as empty.
+fn check_view(template: &Template, index: &Path) -> Vec<Diagnostic> {
+    let source = match fs::read_to_string(&template.view) {
+        Ok(source) => source,
+        Err(..) => {
+            return vec![Diagnostic::new(
+                index,
+                None,
+                format!("view file {} does not exist", template.view.display()),
+            )]
+        }
+    };
+
+    let view_name = template.view.to_string_lossy();
+    let parsed = match tera::Template::new(&view_name, None, &source) {
+        Ok(parsed) => parsed,
+        Err(error) => {
+            let message = match error.source() {
+                Some(source) => format!("{}: {}", error, source),
+                None => error.to_string(),
+            };
+            return vec![Diagnostic::new(&template.view, None, message)];
+        }
+    };
+
+    let mut variables = Variables::default();
+    variables.visit_nodes(&parsed.ast);
+    variables
+        .used
+        .iter()
+        .filter(|name| {
+            !variables.locals.contains(*name)
+                && !template.fields.contains_key(*name)
+                && !template.areas.contains_key(*name)
+        })
+        .map(|name| {
+            Diagnostic::warning(
+                &template.view,
+                find_line(&source, name),
+                format!("{} is neither a declared field nor an area", name),
+            )
+        })
+        .collect()
+}
+
+/// First line, starting at 1, containing the text
+fn find_line(source: &str, text: &str) -> Option<usize> {
+    source
+        .lines()
+        .position(|line| line.contains(text))
+        .map(|index| index + 1)
+}
+
+/// Variables read by a tera template and the ones it defines itself
+#[derive(Default)]
+struct Variables {
+    used: BTreeSet<String>,
+    locals: BTreeSet<String>,
+}
+
+impl Variables {
+    fn visit_nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.visit_node(node);
+        }
+    }
+
+    fn visit_node(&mut self, node: &Node) {
+        match node {
+            Node::VariableBlock(_, expr) => self.visit_expr(expr),
+            Node::MacroDefinition(_, definition, _) => {
+                self.locals.extend(definition.args.keys().cloned());
+                self.visit_nodes(&definition.body);
+            }
+            Node::Set(_, set) => {
+                self.visit_expr(&set.value);
+                self.locals.insert(set.key.to_owned());
+            }
+            Node::FilterSection(_, section, _) => {
+                section
+                    .filter
+                    .args
+                    .values()
+                    .for_each(|e| self.visit_expr(e));
+                self.visit_nodes(&section.body);
+            }
+            Node::Block(_, block, _) => self.visit_nodes(&block.body),
+            Node::Forloop(_, forloop, _) => {
+                self.visit_expr(&forloop.container);
+                self.locals.insert("loop".to_string());
+                self.locals.insert(forloop.value.to_owned());
+                self.locals.extend(forloop.key.iter().cloned());
+                self.visit_nodes(&forloop.body);
+                if let Some(body) = &forloop.empty_body {
+                    self.visit_nodes(body);
+                }
+            }
+            Node::If(condition, _) => {
+                for (_, expr, body) in &condition.conditions {
+                    self.visit_expr(expr);
+                    self.visit_nodes(body);
+                }
+                if let Some((_, body)) = &condition.otherwise {
+                    self.visit_nodes(body);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        self.visit_value(&expr.val);
+        for filter in &expr.filters {
+            filter.args.values().for_each(|e| self.visit_expr(e));
+        }
+    }
+
+    fn visit_value(&mut self, value: &ExprVal) {
+        match value {
+            ExprVal::Ident(ident) => self.use_ident(ident),
+            ExprVal::Math(math) => {
+                self.visit_expr(&math.lhs);
+                self.visit_expr(&math.rhs);
+            }
+            ExprVal::Logic(logic) => {
+                self.visit_expr(&logic.lhs);
+                self.visit_expr(&logic.rhs);
+            }
+            ExprVal::Test(test) => {
+                self.use_ident(&test.ident);
+                test.args.iter().for_each(|e| self.visit_expr(e));
+            }
+            ExprVal::MacroCall(call) => call.args.values().for_each(|e| self.visit_expr(e)),
+            ExprVal::FunctionCall(call) => call.args.values().for_each(|e| self.visit_expr(e)),
+            ExprVal::Array(values) => values.iter().for_each(|e| self.visit_expr(e)),
+            ExprVal::StringConcat(concat) => concat.values.iter().for_each(|v| self.visit_value(v)),
+            ExprVal::In(contains) => {
+                self.visit_expr(&contains.lhs);
+                self.visit_expr(&contains.rhs);
+            }
+            ExprVal::String(..) | ExprVal::Int(..) | ExprVal::Float(..) | ExprVal::Bool(..) => {}
+        }
+    }
+
+    /// Keep the root of `title.text` or `links[0]`
+    fn use_ident(&mut self, ident: &str) {
+        let root = ident.split(['.', '[']).next().unwrap_or(ident);
+        if root != "__tera_context" {
+            self.used.insert(root.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod view {
+        use crate::theme::check::Variables;
+
+        #[test]
+        fn collect_variables() {
+            let source = r#"
+{% set greeting = "hello" %}
+<h1>{{ title | upper }}</h1>
+{% for link in links %}<a href="{{ link.url }}">{{ loop.index }}</a>{% endfor %}
+{% if user.name and show %}{{ greeting }}{% endif %}
+{{ content | area }}
+"#;
+            let parsed = tera::Template::new("view", None, source).unwrap();
+            let mut variables = Variables::default();
+            variables.visit_nodes(&parsed.ast);
+
+            let used: Vec<&str> = variables.used.iter().map(|s| s.as_str()).collect();
+            assert_eq!(
+                used,
+                vec!["content", "greeting", "link", "links", "loop", "show", "title", "user"]
+            );
+            assert!(variables.locals.contains("link"));
+            assert!(variables.locals.contains("greeting"));
+        }
+    }
+}
@@ -0,0 +1,555 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use regex::Regex;
+use serde::{
+    de::{self, value::MapAccessDeserializer, IntoDeserializer, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use serde_yaml::Value;
+
+use super::{names_template, Field};
+use crate::error::FieldError;
+
+/// A field declared by a template, either by its kind alone or by a map
+/// adding the rules its value follows and how the editor presents it
+///
+/// ```yaml
+/// fields:
+///   title:
+///     kind: string
+///     required: true
+///     max_length: 80
+///   rating:
+///     kind: number
+///     min: 1
+///     max: 5
+///     default: 3
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldDefinition {
+    pub kind: Field,
+    /// The value cannot be left empty
+    pub required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    /// Counted in characters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<Pattern>,
+    /// Value of the field in a new module, instead of the one of its kind
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub help: Option<String>,
+}
+
+impl FieldDefinition {
+    /// Problems of a value, each one names the path of the field it is
+    /// about, like `links.0.url` for a field of a list item. Required fields
+    /// may be left empty unless the value must be `complete`.
+    pub(crate) fn validate(&self, path: &str, value: &Value, complete: bool) -> Vec<FieldError> {
+        if complete && self.required && is_empty(value) {
+            return vec![FieldError::new(path, "field is required")];
+        }
+
+        match (&self.kind, value) {
+            (Field::List(fields), Value::Sequence(items)) => {
+                let mut errors: Vec<FieldError> = self
+                    .check_count(items.len())
+                    .err()
+                    .map(|message| FieldError::new(path, &message))
+                    .into_iter()
+                    .collect();
+                for (index, item) in items.iter().enumerate() {
+                    let path = join(path, &index.to_string());
+                    errors.extend(validate_object(fields, &path, item, complete));
+                }
+                errors
+            }
+            (Field::List(..), _) => vec![FieldError::new(path, "expected a list")],
+            (Field::Object(fields), value) => validate_object(fields, path, value, complete),
+            (_, value) => self
+                .check(value)
+                .err()
+                .map(|message| FieldError::new(path, &message))
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Rules of a single value
+    fn check(&self, value: &Value) -> Result<(), String> {
+        self.kind.validate(value)?;
+
+        match value {
+            Value::Number(number) => {
+                let number = number.as_f64().unwrap_or_default();
+                match (self.min, self.max) {
+                    (Some(min), _) if number < min => Err(format!("must be at least {}", min)),
+                    (_, Some(max)) if number > max => Err(format!("must be at most {}", max)),
+                    _ => Ok(()),
+                }
+            }
+            // an optional text left empty has nothing more to check
+            Value::String(text) if text.is_empty() => Ok(()),
+            Value::String(text) => {
+                let length = text.chars().count();
+                match (self.min_length, self.max_length, &self.pattern) {
+                    (Some(min), _, _) if length < min => {
+                        Err(format!("must be at least {} characters long", min))
+                    }
+                    (_, Some(max), _) if length > max => {
+                        Err(format!("must be at most {} characters long", max))
+                    }
+                    (_, _, Some(pattern)) if !pattern.matches(text) => {
+                        Err("does not match the expected format".to_string())
+                    }
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Length rules of a list apply to its number of items
+    fn check_count(&self, count: usize) -> Result<(), String> {
+        match (self.min_length, self.max_length) {
+            (Some(min), _) if count < min => Err(format!("must have at least {} items", min)),
+            (_, Some(max)) if count > max => Err(format!("must have at most {} items", max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Problems of named values against the fields declaring them, `saved`
+/// gives the value kept by a field that was not submitted
+pub(crate) fn validate_values<'a, 'v>(
+    fields: &BTreeMap<String, FieldDefinition>,
+    path: &str,
+    values: impl Iterator<Item = (&'a str, &'v Value)>,
+    saved: impl Fn(&str) -> Option<&'v Value>,
+    complete: bool,
+) -> Vec<FieldError> {
+    let mut errors = vec![];
+    let mut submitted = BTreeSet::new();
+    for (name, value) in values {
+        submitted.insert(name);
+        match fields.get(name) {
+            Some(field) => errors.extend(field.validate(&join(path, name), value, complete)),
+            None => errors.push(FieldError::new(
+                &join(path, name),
+                "field is not declared by the template",
+            )),
+        }
+    }
+    errors.extend(
+        fields
+            .iter()
+            .filter(|(name, field)| {
+                complete
+                    && field.required
+                    && !submitted.contains(name.as_str())
+                    && saved(name).is_none_or(is_empty)
+            })
+            .map(|(name, _)| FieldError::new(&join(path, name), "field is required")),
+    );
+    errors.sort_by(|a, b| a.field.cmp(&b.field));
+    errors
+}
+
+fn validate_object(
+    fields: &BTreeMap<String, FieldDefinition>,
+    path: &str,
+    value: &Value,
+    complete: bool,
+) -> Vec<FieldError> {
+    match value {
+        Value::Mapping(mapping) => {
+            let values = mapping
+                .iter()
+                .map(|(name, value)| (name.as_str().unwrap_or_default(), value));
+            validate_values(fields, path, values, |_| None, complete)
+        }
+        _ => vec![FieldError::new(path, "expected an object")],
+    }
+}
+
+fn join(path: &str, name: &str) -> String {
+    match path {
+        "" => name.to_string(),
+        path => format!("{}.{}", path, name),
+    }
+}
+
+/// Whether a value counts as not filled in
+pub(crate) fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::String(text) => text.is_empty(),
+        Value::Sequence(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+impl From<Field> for FieldDefinition {
+    fn from(kind: Field) -> Self {
+        Self {
+            kind,
+            required: false,
+            min: None,
+            max: None,
+            min_length: None,
+            max_length: None,
+            pattern: None,
+            default: None,
+            label: None,
+            help: None,
+        }
+    }
+}
+
+/// The extended form of a declaration, `select`, `page_ref`, `list` and
+/// `object` are accepted instead of `kind` so these fields keep their short
+/// form
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DefinitionMap {
+    #[serde(default, deserialize_with = "deserialize_kind")]
+    kind: Option<Field>,
+    select: Option<Vec<String>>,
+    page_ref: Option<PageRef>,
+    list: Option<BTreeMap<String, FieldDefinition>>,
+    object: Option<BTreeMap<String, FieldDefinition>>,
+    #[serde(default)]
+    required: bool,
+    min: Option<f64>,
+    max: Option<f64>,
+    min_length: Option<usize>,
+    max_length: Option<usize>,
+    pattern: Option<Pattern>,
+    default: Option<Value>,
+    label: Option<String>,
+    help: Option<String>,
+}
+
+struct DefinitionVisitor;
+
+impl<'de> Visitor<'de> for DefinitionVisitor {
+    type Value = FieldDefinition;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a field kind or a map with the kind and rules of the field"
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, kind: &str) -> Result<Self::Value, E> {
+        KindVisitor.visit_str(kind).map(FieldDefinition::from)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        let map = DefinitionMap::deserialize(MapAccessDeserializer::new(map))?;
+        let mut kinds = map
+            .kind
+            .into_iter()
+            .chain(map.select.map(Field::Select))
+            .chain(map.page_ref.map(Field::PageRef))
+            .chain(map.list.map(Field::List))
+            .chain(map.object.map(Field::Object));
+        let kind = match (kinds.next(), kinds.next()) {
+            (Some(kind), None) => kind,
+            (None, _) => return Err(de::Error::missing_field("kind")),
+            (Some(..), Some(..)) => {
+                return Err(de::Error::custom(
+                    "a field sets only one of kind, select, page_ref, list or object",
+                ))
+            }
+        };
+        Ok(FieldDefinition {
+            kind,
+            required: map.required,
+            min: map.min,
+            max: map.max,
+            min_length: map.min_length,
+            max_length: map.max_length,
+            pattern: map.pattern,
+            default: map.default,
+            label: map.label,
+            help: map.help,
+        })
+    }
+}
+
+/// Reads a kind written alone, where `page_ref` needs no restriction
+struct KindVisitor;
+
+impl<'de> Visitor<'de> for KindVisitor {
+    type Value = Field;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a field kind")
+    }
+
+    fn visit_str<E: de::Error>(self, kind: &str) -> Result<Self::Value, E> {
+        match kind {
+            "page_ref" => Ok(Field::PageRef(PageRef::default())),
+            kind => Field::deserialize(kind.into_deserializer()),
+        }
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        Field::deserialize(MapAccessDeserializer::new(map))
+    }
+}
+
+fn deserialize_kind<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Field>, D::Error> {
+    deserializer.deserialize_any(KindVisitor).map(Some)
+}
+
+impl<'de> Deserialize<'de> for FieldDefinition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DefinitionVisitor)
+    }
+}
+
+/// A field holding the id of another page, views get the url, the name and
+/// the listed fields of the published page instead
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct PageRef {
+    /// Templates the page must use, any page when empty
+    #[serde(default)]
+    pub templates: Vec<String>,
+    /// Fields of the page given to views
+    #[serde(default)]
+    pub fields: Vec<String>,
+}
+
+impl PageRef {
+    pub fn accepts(&self, template: &str) -> bool {
+        self.templates.is_empty()
+            || self
+                .templates
+                .iter()
+                .any(|name| names_template(name, template))
+    }
+}
+
+/// A page or media id held by a field, `path` names the field like
+/// validation errors
+#[derive(Debug)]
+pub(crate) struct HeldId<'a> {
+    pub path: String,
+    pub kind: &'a Field,
+    pub id: &'a str,
+}
+
+impl Field {
+    /// Whether the value of the field is the id of a page or of a media
+    fn holds_id(&self) -> bool {
+        matches!(self, Field::PageRef(..) | Field::Image | Field::File)
+    }
+}
+
+/// Page and media ids held by named values, nested lists and objects
+/// included
+pub(crate) fn held_ids<'a>(
+    fields: &'a BTreeMap<String, FieldDefinition>,
+    path: &str,
+    values: impl Iterator<Item = (&'a str, &'a Value)>,
+) -> Vec<HeldId<'a>> {
+    let mut ids = vec![];
+    for (name, value) in values {
+        if let Some(field) = fields.get(name) {
+            collect_ids(&field.kind, &join(path, name), value, &mut ids);
+        }
+    }
+    ids
+}
+
+fn collect_ids<'a>(kind: &'a Field, path: &str, value: &'a Value, ids: &mut Vec<HeldId<'a>>) {
+    match (kind, value) {
+        (kind, Value::String(id)) if kind.holds_id() && !id.is_empty() => ids.push(HeldId {
+            path: path.to_string(),
+            kind,
+            id,
+        }),
+        (Field::List(fields), Value::Sequence(items)) => {
+            for (index, item) in items.iter().enumerate() {
+                collect_object_ids(fields, &join(path, &index.to_string()), item, ids);
+            }
+        }
+        (Field::Object(fields), value) => collect_object_ids(fields, path, value, ids),
+        _ => {}
+    }
+}
+
+fn collect_object_ids<'a>(
+    fields: &'a BTreeMap<String, FieldDefinition>,
+    path: &str,
+    value: &'a Value,
+    ids: &mut Vec<HeldId<'a>>,
+) {
+    if let Value::Mapping(mapping) = value {
+        let values = mapping
+            .iter()
+            .filter_map(|(name, value)| Some((name.as_str()?, value)));
+        ids.extend(held_ids(fields, path, values));
+    }
+}
+
+/// Replace the ids held by a value with what `resolve` gives for them
+pub(crate) fn resolve_ids(
+    kind: &Field,
+    value: &mut Value,
+    resolve: &dyn Fn(&Field, &str) -> Value,
+) {
+    match (kind, value) {
+        (kind, value) if kind.holds_id() => {
+            *value = match value {
+                Value::String(id) if !id.is_empty() => resolve(kind, id),
+                _ => Value::Null,
+            }
+        }
+        (Field::List(fields), Value::Sequence(items)) => {
+            for item in items {
+                resolve_object_ids(fields, item, resolve);
+            }
+        }
+        (Field::Object(fields), value) => resolve_object_ids(fields, value, resolve),
+        _ => {}
+    }
+}
+
+fn resolve_object_ids(
+    fields: &BTreeMap<String, FieldDefinition>,
+    value: &mut Value,
+    resolve: &dyn Fn(&Field, &str) -> Value,
+) {
+    if let Value::Mapping(mapping) = value {
+        for (name, value) in mapping.iter_mut() {
+            if let Some(field) = name.as_str().and_then(|name| fields.get(name)) {
+                resolve_ids(&field.kind, value, resolve);
+            }
+        }
+    }
+}
+
+/// A regular expression the whole text must match
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    source: String,
+    regex: Regex,
+}
+
+impl Pattern {
+    pub fn matches(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let source = String::deserialize(deserializer)?;
+        let regex = Regex::new(&format!("^(?:{})$", source)).map_err(de::Error::custom)?;
+        Ok(Self { source, regex })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod field {
+        use crate::{error::FieldError, theme::FieldDefinition};
+        use serde_yaml::Value;
+
+        fn definition(yaml: &str) -> FieldDefinition {
+            serde_yaml::from_str(yaml).unwrap()
+        }
+
+        #[test]
+        fn validate_values_of_the_wrong_kind() {
+            let links = definition("list:\n  label: string");
+            assert_eq!(
+                links.validate("links", &Value::from("home"), true),
+                vec![FieldError::new("links", "expected a list")]
+            );
+            let seo = definition("object:\n  title: string");
+            assert_eq!(
+                seo.validate("seo", &Value::from(3), true),
+                vec![FieldError::new("seo", "expected an object")]
+            );
+            let title = definition("string");
+            assert_eq!(
+                title.validate("title", &Value::Sequence(vec![]), true),
+                vec![FieldError::new("title", "expected a string")]
+            );
+            let date = definition("date");
+            assert_eq!(
+                date.validate("day", &Value::from("soon"), true),
+                vec![FieldError::new("day", "expected a date as YYYY-MM-DD")]
+            );
+        }
+
+        #[test]
+        fn leave_required_fields_empty_in_drafts() {
+            let title = definition("kind: string\nrequired: true\nmax_length: 3");
+            assert!(title.validate("title", &Value::from(""), false).is_empty());
+            assert_eq!(
+                title.validate("title", &Value::from(""), true),
+                vec![FieldError::new("title", "field is required")]
+            );
+            assert_eq!(
+                title.validate("title", &Value::from("long"), false),
+                vec![FieldError::new(
+                    "title",
+                    "must be at most 3 characters long"
+                )]
+            );
+        }
+
+        #[test]
+        fn validate_rules() {
+            let rating = definition("kind: number\nmin: 1\nmax: 5");
+            assert!(rating.validate("rating", &Value::from(3), true).is_empty());
+            assert_eq!(
+                rating.validate("rating", &Value::from(0), true),
+                vec![FieldError::new("rating", "must be at least 1")]
+            );
+            let links = definition("list:\n  url: url\nmax_length: 1");
+            let items: Value = serde_yaml::from_str("[{url: /}, {url: nowhere}]").unwrap();
+            assert_eq!(
+                links.validate("links", &items, true),
+                vec![
+                    FieldError::new("links", "must have at most 1 items"),
+                    FieldError::new(
+                        "links.1.url",
+                        "expected an http, https or mailto url or a path"
+                    ),
+                ]
+            );
+        }
+    }
+}
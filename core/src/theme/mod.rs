@@ -0,0 +1,741 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+use crate::{
+    error::{Error, FieldError, Result},
+    module::Module,
+};
+
+pub use check::{Diagnostic, Severity};
+pub(crate) use field::HeldId;
+pub use field::{FieldDefinition, PageRef, Pattern};
+
+mod check;
+mod field;
+
+#[derive(Debug, Deserialize)]
+pub struct Template {
+    pub view: PathBuf,
+    /// Content of the view when the theme was loaded, what the renderer
+    /// compiles
+    #[serde(skip)]
+    pub source: String,
+    #[serde(default)]
+    pub fields: BTreeMap<String, FieldDefinition>,
+    #[serde(default)]
+    pub areas: HashMap<String, Area>,
+}
+
+impl Template {
+    /// Problems of values against the fields declared by the template. When
+    /// `complete`, a required field must also be filled once merged with the
+    /// saved values.
+    pub(crate) fn field_errors(
+        &self,
+        saved: &HashMap<String, Value>,
+        values: &HashMap<String, Value>,
+        complete: bool,
+    ) -> Vec<FieldError> {
+        let values = values.iter().map(|(name, value)| (name.as_str(), value));
+        field::validate_values(&self.fields, "", values, |name| saved.get(name), complete)
+    }
+
+    /// Page and media ids held by the values, nested lists and objects
+    /// included
+    pub(crate) fn held_ids<'a>(&'a self, values: &'a HashMap<String, Value>) -> Vec<HeldId<'a>> {
+        let values = values.iter().map(|(name, value)| (name.as_str(), value));
+        field::held_ids(&self.fields, "", values)
+    }
+
+    /// Replace the page and media ids held by the values with what
+    /// `resolve` gives for them
+    pub(crate) fn resolve_ids(
+        &self,
+        values: &mut HashMap<String, Value>,
+        resolve: &dyn Fn(&Field, &str) -> Value,
+    ) {
+        for (name, value) in values.iter_mut() {
+            if let Some(field) = self.fields.get(name) {
+                field::resolve_ids(&field.kind, value, resolve);
+            }
+        }
+    }
+
+    /// Clean the values whose kind needs it, once they are validated
+    pub(crate) fn sanitize_fields(&self, values: &mut HashMap<String, Value>) {
+        for (name, value) in values.iter_mut() {
+            if let Some(field) = self.fields.get(name) {
+                field.kind.sanitize(value);
+            }
+        }
+    }
+}
+
+impl TryFrom<&PathBuf> for Template {
+    type Error = Error;
+
+    fn try_from(path_buf: &PathBuf) -> Result<Self> {
+        let content = fs::read(path_buf)?;
+        let mut template = Template::try_from(content)?;
+        let folder = path_buf.parent().ok_or(Error::ParseTheme)?;
+        template.view = folder.join(template.view);
+        // a missing view is reported by the theme check
+        template.source = fs::read_to_string(&template.view).unwrap_or_default();
+        Ok(template)
+    }
+}
+
+impl TryFrom<&str> for Template {
+    type Error = Error;
+
+    fn try_from(slice: &str) -> Result<Self> {
+        Template::try_from(slice.as_bytes())
+    }
+}
+
+impl TryFrom<&[u8]> for Template {
+    type Error = Error;
+
+    fn try_from(slice: &[u8]) -> Result<Self> {
+        Ok(serde_yaml::from_slice(slice)?)
+    }
+}
+
+impl TryFrom<Vec<u8>> for Template {
+    type Error = Error;
+
+    fn try_from(slice: Vec<u8>) -> Result<Self> {
+        Template::try_from(slice.as_slice())
+    }
+}
+
+/// Kind of value a field holds, `select` lists the allowed options while
+/// `list` and `object` declare the fields of their items
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    String,
+    /// Multi-line plain text
+    Text,
+    /// Markdown source, rendered to html in views
+    Markdown,
+    /// Html restricted to harmless tags, sanitized on save
+    RichText,
+    Number,
+    Boolean,
+    /// A `YYYY-MM-DD` day
+    Date,
+    /// An RFC 3339 date and time
+    Datetime,
+    /// An http, https or mailto url, or a path of the site
+    Url,
+    Email,
+    /// A `#rgb` or `#rrggbb` color
+    Color,
+    Select(Vec<String>),
+    /// The id of another page, `page_ref` alone accepts any page
+    PageRef(PageRef),
+    /// The id of an image of the media library
+    Image,
+    /// The id of any file of the media library
+    File,
+    /// Items sharing the same fields
+    List(BTreeMap<String, FieldDefinition>),
+    /// A group of named fields
+    Object(BTreeMap<String, FieldDefinition>),
+}
+
+impl Field {
+    fn validate(&self, value: &Value) -> std::result::Result<(), &'static str> {
+        let text = match (self, value) {
+            (Field::Number, Value::Number(..)) => return Ok(()),
+            (Field::Boolean, Value::Bool(..)) => return Ok(()),
+            // compound values are checked item by item by their definition
+            (Field::List(..), Value::Sequence(..)) => return Ok(()),
+            (Field::Object(..), Value::Mapping(..)) => return Ok(()),
+            (_, Value::String(text)) => text,
+            (_, _) => return Err(self.expected()),
+        };
+
+        let valid = match self {
+            Field::String | Field::Text | Field::Markdown | Field::RichText => true,
+            // optional values are left empty
+            Field::Date | Field::Datetime | Field::Url | Field::Email if text.is_empty() => true,
+            Field::Date => chrono::NaiveDate::parse_from_str(text, "%Y-%m-%d").is_ok(),
+            Field::Datetime => chrono::DateTime::parse_from_rfc3339(text).is_ok(),
+            Field::Url => is_url(text),
+            Field::Email => is_email(text),
+            Field::Color => is_color(text),
+            Field::Select(options) => options.contains(text),
+            // whether the page or the media exists is checked by the site
+            Field::PageRef(..) | Field::Image | Field::File => true,
+            // not held as text
+            Field::Number | Field::Boolean | Field::List(..) | Field::Object(..) => false,
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(self.expected()),
+        }
+    }
+
+    /// What a value of the field must be, for the error of an invalid one
+    fn expected(&self) -> &'static str {
+        match self {
+            Field::String
+            | Field::Text
+            | Field::Markdown
+            | Field::RichText
+            | Field::PageRef(..)
+            | Field::Image
+            | Field::File => "expected a string",
+            Field::Number => "expected a number",
+            Field::Boolean => "expected a boolean",
+            Field::Date => "expected a date as YYYY-MM-DD",
+            Field::Datetime => "expected an RFC 3339 date and time",
+            Field::Url => "expected an http, https or mailto url or a path",
+            Field::Email => "expected an email address",
+            Field::Color => "expected a color as #rgb or #rrggbb",
+            Field::Select(..) => "expected one of the options",
+            Field::List(..) => "expected a list",
+            Field::Object(..) => "expected an object",
+        }
+    }
+
+    /// Strip rich text from scripts, styles and event handlers
+    fn sanitize(&self, value: &mut Value) {
+        match (self, value) {
+            (Field::RichText, Value::String(text)) => *text = ammonia::clean(text),
+            (Field::List(fields), Value::Sequence(items)) => {
+                for item in items {
+                    sanitize_object(fields, item);
+                }
+            }
+            (Field::Object(fields), value) => sanitize_object(fields, value),
+            _ => {}
+        }
+    }
+}
+
+fn sanitize_object(fields: &BTreeMap<String, FieldDefinition>, value: &mut Value) {
+    if let Value::Mapping(mapping) = value {
+        for (name, value) in mapping.iter_mut() {
+            if let Some(field) = name.as_str().and_then(|name| fields.get(name)) {
+                field.kind.sanitize(value);
+            }
+        }
+    }
+}
+
+/// Only schemes harmless in a link are accepted, `javascript:` or `data:`
+/// urls would run in the page. Paths starting with `//` or `/\` lead to
+/// another host.
+fn is_url(text: &str) -> bool {
+    if let Some(path) = text.strip_prefix('/') {
+        return !path.starts_with(['/', '\\']);
+    }
+    match url::Url::parse(text) {
+        Ok(url) => matches!(url.scheme(), "http" | "https" | "mailto"),
+        Err(..) => false,
+    }
+}
+
+fn is_email(text: &str) -> bool {
+    match text.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.contains('.')
+                && !domain.starts_with('.')
+                && !domain.ends_with('.')
+                && !text.contains(char::is_whitespace)
+        }
+        None => false,
+    }
+}
+
+fn is_color(text: &str) -> bool {
+    match text.strip_prefix('#') {
+        Some(hex) => matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "accept", content = "components", rename_all = "snake_case")]
+pub enum Area {
+    All,
+    Without(Vec<String>),
+    With(Vec<String>),
+}
+
+impl Area {
+    /// Whether a component template can be placed in the area, components
+    /// are listed by their full template name or by their folder name
+    pub fn accepts(&self, template: &str) -> bool {
+        if is_page(template) {
+            return false;
+        }
+        let listed = |components: &Vec<String>| {
+            components
+                .iter()
+                .any(|component| names_template(component, template))
+        };
+        match self {
+            Area::All => true,
+            Area::With(components) => listed(components),
+            Area::Without(components) => !listed(components),
+        }
+    }
+}
+
+/// Whether a component listed in an area designates the template
+fn names_template(component: &str, template: &str) -> bool {
+    let component = component.trim_start_matches('/');
+    template.trim_start_matches('/') == component || template.ends_with(&format!("/{}", component))
+}
+
+/// Templates of the `pages` folder are pages, every other one is a component
+pub(crate) fn is_page(template: &str) -> bool {
+    template.starts_with("/pages/")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateKind {
+    /// Template of the `pages` folder, a page is created from it
+    Page,
+    /// Any other template, placed in the areas of a page
+    Component,
+}
+
+/// What an editor needs to build the form of a template: its fields and the
+/// rules of its areas
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TemplateDefinition {
+    pub kind: TemplateKind,
+    pub fields: BTreeMap<String, FieldDefinition>,
+    pub areas: BTreeMap<String, AreaDefinition>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AreaDefinition {
+    /// The rule as written in the template
+    pub rule: Area,
+    /// Components of the theme the rule lets in, sorted by name
+    pub accepted: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Theme {
+    pub templates: HashMap<String, Template>,
+    pub folder: PathBuf,
+}
+
+impl Theme {
+    /// Load a theme, refusing it when an error is found in its templates,
+    /// warnings are left to the theme check
+    pub(crate) fn from_folder(base_path: &Path) -> Result<Self> {
+        let (theme, diagnostics) = Self::load(base_path)?;
+        if !diagnostics.iter().any(Diagnostic::is_error) {
+            Ok(theme)
+        } else {
+            Err(Error::InvalidTheme(diagnostics))
+        }
+    }
+
+    /// Parse every template of the folder, templates which cannot be parsed
+    /// are left out and reported with every other problem of the theme.
+    // TODO: Change logic and use something else then OsStr for templates...
+    pub(crate) fn load(base_path: &Path) -> Result<(Self, Vec<Diagnostic>)> {
+        let mut templates = HashMap::new();
+        let mut diagnostics = vec![];
+
+        let canonical = get_canonical(base_path)?;
+
+        let pattern = format!("{}{}", canonical, "/**/index.yaml");
+        for entry in glob::glob(&pattern).map_err(|_| Error::ParseTheme)? {
+            let mut path = entry?;
+            let template = match Template::try_from(&path) {
+                Ok(template) => template,
+                Err(error) => {
+                    diagnostics.push(Diagnostic::from_error(&path, error));
+                    continue;
+                }
+            };
+            path.pop();
+            let parent_canonical = get_canonical(&path)?;
+            let name = parent_canonical
+                .split(&canonical)
+                .last()
+                .ok_or(Error::ParseTheme)?;
+            templates.insert(name.to_string(), template);
+        }
+
+        let theme = Self {
+            templates,
+            folder: base_path.to_path_buf(),
+        };
+        diagnostics.extend(check::check_theme(&theme, &canonical));
+        diagnostics.sort();
+        Ok((theme, diagnostics))
+    }
+
+    pub(crate) fn get_area(&self, template_name: &str, area_name: &str) -> Result<&Area> {
+        self.templates
+            .get(template_name)
+            .ok_or(Error::TemplateNotFound)?
+            .areas
+            .get(area_name)
+            .ok_or(Error::AreaNotFound)
+    }
+
+    /// Components accepted by an area of a template, sorted by name
+    pub(crate) fn accepted_components(
+        &self,
+        template_name: &str,
+        area_name: &str,
+    ) -> Result<Vec<String>> {
+        let area = self.get_area(template_name, area_name)?;
+        let mut components: Vec<String> = self
+            .templates
+            .keys()
+            .filter(|name| area.accepts(name))
+            .cloned()
+            .collect();
+        components.sort();
+        Ok(components)
+    }
+
+    pub(crate) fn definition(&self, template_name: &str) -> Result<TemplateDefinition> {
+        let template = self
+            .templates
+            .get(template_name)
+            .ok_or(Error::TemplateNotFound)?;
+        let fields = template
+            .fields
+            .iter()
+            .map(|(name, field)| (name.to_owned(), field.clone()))
+            .collect();
+        let areas = template
+            .areas
+            .iter()
+            .map(|(name, area)| {
+                let definition = AreaDefinition {
+                    rule: area.clone(),
+                    accepted: self.accepted_components(template_name, name)?,
+                };
+                Ok((name.to_owned(), definition))
+            })
+            .collect::<Result<_>>()?;
+        let kind = match is_page(template_name) {
+            true => TemplateKind::Page,
+            false => TemplateKind::Component,
+        };
+        Ok(TemplateDefinition {
+            kind,
+            fields,
+            areas,
+        })
+    }
+
+    pub(crate) fn get_module_defaults(&self, template_name: &str) -> Result<Module> {
+        let template = self
+            .templates
+            .get(template_name)
+            .ok_or(Error::TemplateNotFound)?;
+
+        let fields = template
+            .fields
+            .iter()
+            .map(|(name, field)| (name.to_owned(), field.into()))
+            .collect();
+
+        let areas = template
+            .areas
+            .keys()
+            .map(|name| (name.to_owned(), vec![]))
+            .collect();
+
+        Ok(Module {
+            template: template_name.to_string(),
+            fields,
+            areas,
+        })
+    }
+}
+
+fn get_canonical(path: &Path) -> Result<String> {
+    let canonical = path.canonicalize()?;
+    let canonical = canonical.to_str().ok_or(Error::ParseTheme)?;
+    Ok(canonical.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    mod theme {
+        use crate::theme::Template;
+        use std::path::PathBuf;
+
+        #[test]
+        fn create_new_template() {
+            let template_data = r#"
+--- 
+view: view.html
+fields: 
+  field_1: string
+areas: 
+  areas_1: 
+    accept: without
+    components: 
+      - template_1
+  areas_2: 
+    accept: with
+    components: 
+      - template_1
+  areas_3: 
+    accept: all
+        "#;
+
+            let template = Template::try_from(template_data).unwrap();
+
+            assert_eq!(template.view, PathBuf::from("view.html"));
+            assert_eq!(template.fields.len(), 1);
+            assert_eq!(template.areas.len(), 3);
+        }
+
+        #[test]
+        fn area_accepts() {
+            use crate::theme::Area;
+
+            let with = Area::With(vec!["header".to_string()]);
+            assert!(with.accepts("/components/header"));
+            assert!(!with.accepts("/components/image"));
+            assert!(!with.accepts("/pages/header"));
+
+            let without = Area::Without(vec!["/components/header".to_string()]);
+            assert!(!without.accepts("/components/header"));
+            assert!(without.accepts("/components/image"));
+
+            assert!(Area::All.accepts("/components/image"));
+            assert!(!Area::All.accepts("/pages/article"));
+        }
+
+        #[test]
+        fn validate_fields() {
+            use crate::error::FieldError;
+            use serde_yaml::Value;
+            use std::collections::HashMap;
+
+            let template =
+                Template::try_from("view: view.html\nfields:\n  title: string\n  count: number")
+                    .unwrap();
+
+            let mut values = HashMap::new();
+            values.insert("title".to_string(), Value::from("hello"));
+            values.insert("count".to_string(), Value::from(3));
+            assert!(template
+                .field_errors(&HashMap::new(), &values, true)
+                .is_empty());
+
+            values.insert("count".to_string(), Value::from("three"));
+            values.insert("unknown".to_string(), Value::from(true));
+            assert_eq!(
+                template.field_errors(&HashMap::new(), &values, true),
+                vec![
+                    FieldError::new("count", "expected a number"),
+                    FieldError::new("unknown", "field is not declared by the template"),
+                ]
+            );
+        }
+
+        #[test]
+        fn validate_rich_fields() {
+            use serde_yaml::Value;
+            use std::collections::HashMap;
+
+            let template = Template::try_from(
+                r#"
+view: view.html
+fields:
+  published: date
+  updated: datetime
+  link: url
+  contact: email
+  accent: color
+  layout:
+    select: [wide, narrow]
+  body: rich_text
+"#,
+            )
+            .unwrap();
+
+            let valid = |name: &str, value: &str| {
+                let values = HashMap::from([(name.to_string(), Value::from(value))]);
+                template
+                    .field_errors(&HashMap::new(), &values, true)
+                    .is_empty()
+            };
+            assert!(valid("published", "2024-02-29"));
+            assert!(!valid("published", "2023-02-29"));
+            assert!(valid("published", ""));
+            assert!(valid("updated", "2024-01-01T10:00:00+01:00"));
+            assert!(!valid("updated", "2024-01-01"));
+            assert!(valid("link", "https://example.com/a?b=c"));
+            assert!(valid("link", "/about"));
+            assert!(!valid("link", "example.com"));
+            assert!(valid("link", "mailto:someone@example.com"));
+            assert!(!valid("link", "javascript:alert(1)"));
+            assert!(!valid("link", "JavaScript:alert(1)"));
+            assert!(!valid("link", "data:text/html,<script>alert(1)</script>"));
+            assert!(!valid("link", "//evil.example"));
+            assert!(!valid("link", "/\\evil.example"));
+            assert!(valid("contact", "jane@example.com"));
+            assert!(!valid("contact", "jane@example"));
+            assert!(!valid("contact", "@example.com"));
+            assert!(valid("accent", "#a0f"));
+            assert!(valid("accent", "#00AAff"));
+            assert!(!valid("accent", "red"));
+            assert!(!valid("accent", ""));
+            assert!(valid("layout", "wide"));
+            assert!(!valid("layout", "full"));
+
+            let mut values = HashMap::from([(
+                "body".to_string(),
+                Value::from("<p onclick=\"steal()\">hi<script>steal()</script></p>"),
+            )]);
+            template.sanitize_fields(&mut values);
+            assert_eq!(values["body"], Value::from("<p>hi</p>"));
+        }
+
+        #[test]
+        fn parse_field_definitions() {
+            use crate::theme::Field;
+
+            let template = Template::try_from(
+                r#"
+view: view.html
+fields:
+  title: string
+  layout:
+    select: [wide, narrow]
+  summary:
+    kind: text
+    required: true
+    min_length: 10
+    help: Shown in listings
+"#,
+            )
+            .unwrap();
+
+            assert_eq!(template.fields["title"].kind, Field::String);
+            assert!(!template.fields["title"].required);
+            assert_eq!(
+                template.fields["layout"].kind,
+                Field::Select(vec!["wide".to_string(), "narrow".to_string()])
+            );
+            let summary = &template.fields["summary"];
+            assert_eq!(summary.kind, Field::Text);
+            assert!(summary.required);
+            assert_eq!(summary.min_length, Some(10));
+            assert_eq!(summary.help.as_deref(), Some("Shown in listings"));
+
+            let template = Template::try_from(
+                "view: v\nfields:\n  any: page_ref\n  post:\n    kind: page_ref\n  article:\n    page_ref:\n      templates: [article]\n",
+            )
+            .unwrap();
+            assert_eq!(
+                template.fields["any"].kind,
+                Field::PageRef(Default::default())
+            );
+            assert_eq!(template.fields["post"].kind, template.fields["any"].kind);
+            match &template.fields["article"].kind {
+                Field::PageRef(page_ref) => {
+                    assert!(page_ref.accepts("/pages/article"));
+                    assert!(!page_ref.accepts("/pages/section"));
+                }
+                other => panic!("unexpected kind {:?}", other),
+            }
+
+            assert!(Template::try_from("view: v\nfields:\n  a:\n    required: true").is_err());
+            assert!(
+                Template::try_from("view: v\nfields:\n  a:\n    kind: string\n    size: 3")
+                    .is_err()
+            );
+            assert!(Template::try_from(
+                "view: v\nfields:\n  a:\n    kind: string\n    pattern: '('"
+            )
+            .is_err());
+        }
+
+        #[test]
+        fn validate_compound_fields() {
+            use crate::theme::Field;
+            use serde_yaml::Value;
+            use std::collections::HashMap;
+
+            let template = Template::try_from(
+                r#"
+view: view.html
+fields:
+  links:
+    list:
+      label:
+        kind: string
+        required: true
+      url: url
+    max_length: 2
+  seo:
+    object:
+      title: string
+      body: rich_text
+"#,
+            )
+            .unwrap();
+            assert!(matches!(template.fields["links"].kind, Field::List(..)));
+            assert_eq!(
+                Value::from(&template.fields["seo"]),
+                serde_yaml::from_str::<Value>("{body: '', title: ''}").unwrap()
+            );
+
+            let values: HashMap<String, Value> = serde_yaml::from_str(
+                r#"
+links:
+  - label: Home
+    url: /
+  - url: nowhere
+    target: _blank
+seo: hello
+"#,
+            )
+            .unwrap();
+            let errors = template.field_errors(&HashMap::new(), &values, true);
+            let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            assert_eq!(
+                messages,
+                vec![
+                    "links.1.label: field is required",
+                    "links.1.target: field is not declared by the template",
+                    "links.1.url: expected an http, https or mailto url or a path",
+                    "seo: expected an object",
+                ]
+            );
+
+            let mut values: HashMap<String, Value> = serde_yaml::from_str(
+                "seo:\n  title: Hi\n  body: <b>bold</b><script>x()</script>\n",
+            )
+            .unwrap();
+            assert!(template
+                .field_errors(&HashMap::new(), &values, true)
+                .is_empty());
+            template.sanitize_fields(&mut values);
+            assert_eq!(values["seo"]["body"], Value::from("<b>bold</b>"));
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use serde_yaml::Value;
+
+use crate::{
+    error::{Error, FieldError, Result},
+    media::MediaLibrary,
+    module::Module,
+    reference::reference_errors,
+    store::Store,
+    theme::Theme,
+};
+
+/// Check values written to the fields of a module: they must follow their
+/// declaration, reference existing pages and media, and leave no required
+/// field empty once merged with the saved ones
+pub(crate) fn validate_fields(
+    storage: &dyn Store,
+    media: Option<&MediaLibrary>,
+    theme: &Theme,
+    module: &Module,
+    values: &HashMap<String, Value>,
+) -> Result<()> {
+    let template = theme
+        .templates
+        .get(&module.template)
+        .ok_or(Error::TemplateNotFound)?;
+    let mut errors = template.field_errors(&module.fields, values, true);
+    errors.extend(reference_errors(storage, media, template, values));
+    into_result(errors)
+}
+
+/// Check the fields of a module and of every module in its areas, errors
+/// name the module path of the field, like `area_1/0/title`. A draft may
+/// leave required fields empty, a `complete` module going live may not.
+/// Saved values of fields the theme no longer declares are left alone.
+pub(crate) fn validate_module(
+    storage: &dyn Store,
+    media: Option<&MediaLibrary>,
+    theme: &Theme,
+    module: &Module,
+    complete: bool,
+) -> Result<()> {
+    let mut errors = vec![];
+    collect_errors(storage, media, theme, "", module, complete, &mut errors)?;
+    into_result(errors)
+}
+
+fn collect_errors(
+    storage: &dyn Store,
+    media: Option<&MediaLibrary>,
+    theme: &Theme,
+    path: &str,
+    module: &Module,
+    complete: bool,
+    errors: &mut Vec<FieldError>,
+) -> Result<()> {
+    let template = theme
+        .templates
+        .get(&module.template)
+        .ok_or(Error::TemplateNotFound)?;
+    let values: HashMap<String, Value> = module
+        .fields
+        .iter()
+        .filter(|(name, _)| template.fields.contains_key(*name))
+        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        .collect();
+    let found = template
+        .field_errors(&HashMap::new(), &values, complete)
+        .into_iter()
+        .chain(reference_errors(storage, media, template, &values));
+    errors.extend(
+        found.map(|error| FieldError::new(&format!("{}{}", path, error.field), &error.message)),
+    );
+
+    for (area, modules) in &module.areas {
+        for (index, nested) in modules.iter().enumerate() {
+            let path = format!("{}{}/{}/", path, area, index);
+            collect_errors(storage, media, theme, &path, nested, complete, errors)?;
+        }
+    }
+    Ok(())
+}
+
+fn into_result(mut errors: Vec<FieldError>) -> Result<()> {
+    errors.sort_by(|a, b| a.field.cmp(&b.field));
+    match errors.is_empty() {
+        true => Ok(()),
+        false => Err(Error::Validation(errors)),
+    }
+}
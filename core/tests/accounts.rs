@@ -0,0 +1,164 @@
+use core::{Accounts, Error, Role, UserChanges};
+
+fn accounts() -> (Accounts, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("users.yml");
+    let accounts = Accounts::load(file.to_str().unwrap()).unwrap();
+    (accounts, dir)
+}
+
+fn role(role: Role) -> UserChanges {
+    UserChanges {
+        role: Some(role),
+        ..UserChanges::default()
+    }
+}
+
+#[test]
+fn login_with_hashed_password() {
+    let (accounts, dir) = accounts();
+    assert!(accounts.is_empty());
+    let mut kept = None;
+    let password = accounts
+        .ensure_admin("admin", None, |password| {
+            kept = Some(password.to_owned());
+            Ok(())
+        })
+        .unwrap()
+        .unwrap();
+    assert_eq!(kept.as_ref(), Some(&password));
+    assert_eq!(
+        accounts.ensure_admin("admin", None, |_| Ok(())).unwrap(),
+        None
+    );
+
+    accounts
+        .create_user("alice", "secret", Role::Editor)
+        .unwrap();
+    assert!(matches!(
+        accounts.create_user("alice", "other", Role::Editor),
+        Err(Error::DuplicatedUser)
+    ));
+    let saved = std::fs::read_to_string(dir.path().join("users.yml")).unwrap();
+    assert!(!saved.contains("secret"));
+    assert!(saved.contains("$argon2"));
+
+    assert!(matches!(
+        accounts.login("alice", "wrong"),
+        Err(Error::InvalidCredentials)
+    ));
+    let session = accounts.login("alice", "secret").unwrap();
+    assert_eq!(
+        accounts.authenticate(&session.token).unwrap().role,
+        Role::Editor
+    );
+    accounts.logout(&session.token);
+    assert!(matches!(
+        accounts.authenticate(&session.token),
+        Err(Error::Unauthenticated)
+    ));
+
+    let reloaded = Accounts::load(dir.path().join("users.yml").to_str().unwrap()).unwrap();
+    let admin = reloaded.login("admin", &password).unwrap();
+    assert_eq!(admin.user.role, Role::Admin);
+}
+
+#[test]
+fn manage_roles() {
+    let (accounts, _dir) = accounts();
+    accounts
+        .ensure_admin("admin", Some("admin"), |_| Ok(()))
+        .unwrap();
+    accounts.create_user("bob", "bob", Role::Editor).unwrap();
+    let session = accounts.login("bob", "bob").unwrap();
+
+    assert!(Role::Editor < Role::Developer && Role::Developer < Role::Admin);
+    accounts.update_user("bob", role(Role::Developer)).unwrap();
+    assert_eq!(
+        accounts.authenticate(&session.token).unwrap().role,
+        Role::Developer
+    );
+
+    assert!(matches!(
+        accounts.update_user("admin", role(Role::Editor)),
+        Err(Error::LastAdmin)
+    ));
+    assert!(matches!(
+        accounts.delete_user("admin"),
+        Err(Error::LastAdmin)
+    ));
+
+    let changes = UserChanges {
+        password: Some("new".into()),
+        ..UserChanges::default()
+    };
+    accounts.update_user("bob", changes).unwrap();
+    assert!(accounts.authenticate(&session.token).is_err());
+    accounts.delete_user("bob").unwrap();
+    let names: Vec<String> = accounts.users().into_iter().map(|u| u.name).collect();
+    assert_eq!(names, vec!["admin"]);
+}
+
+#[test]
+fn update_user_at_once() {
+    let (accounts, _dir) = accounts();
+    accounts
+        .ensure_admin("admin", Some("admin"), |_| Ok(()))
+        .unwrap();
+
+    let changes = UserChanges {
+        password: Some("new".into()),
+        role: Some(Role::Editor),
+        groups: Some(vec!["writers".into()]),
+    };
+    assert!(matches!(
+        accounts.update_user("admin", changes),
+        Err(Error::LastAdmin)
+    ));
+    let admin = accounts.get_user("admin").unwrap();
+    assert!(admin.groups.is_empty());
+    accounts.login("admin", "admin").unwrap();
+
+    let changes = UserChanges {
+        groups: Some(vec!["writers".into()]),
+        password: Some("new".into()),
+        ..UserChanges::default()
+    };
+    let admin = accounts.update_user("admin", changes).unwrap();
+    assert_eq!(admin.groups, vec!["writers"]);
+    accounts.login("admin", "new").unwrap();
+}
+
+#[test]
+fn lock_out_failed_logins() {
+    let (accounts, _dir) = accounts();
+    accounts
+        .ensure_admin("admin", Some("admin"), |_| Ok(()))
+        .unwrap();
+
+    for _ in 0..4 {
+        assert!(matches!(
+            accounts.login("admin", "wrong"),
+            Err(Error::InvalidCredentials)
+        ));
+    }
+    accounts.login("admin", "admin").unwrap();
+
+    for _ in 0..5 {
+        assert!(matches!(
+            accounts.login("admin", "wrong"),
+            Err(Error::InvalidCredentials)
+        ));
+    }
+    assert!(matches!(
+        accounts.login("admin", "admin"),
+        Err(Error::TooManyAttempts)
+    ));
+    for _ in 0..5 {
+        let _ = accounts.login("nobody", "wrong");
+    }
+    assert!(matches!(
+        accounts.login("nobody", "wrong"),
+        Err(Error::TooManyAttempts)
+    ));
+}
@@ -0,0 +1,133 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use core::{Clock, Role, Site, SiteBuilder, SystemClock, User};
+use tempfile::TempDir;
+
+const THEME: &str = "./tests/test_site/theme";
+
+/// User allowed everything, for tests not about access rights
+pub fn admin() -> User {
+    User {
+        name: "admin".into(),
+        role: Role::Admin,
+        groups: vec![],
+    }
+}
+
+/// Build a site over the test theme with an empty storage living in a
+/// temporary folder, so tests can run side by side.
+pub fn test_site() -> (Site, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let site = build_site(&dir, THEME, Arc::new(SystemClock));
+    (site, dir)
+}
+
+/// Same as `test_site` but reading the time from `clock`
+#[allow(dead_code)]
+pub fn test_site_with_clock(clock: Arc<dyn Clock>) -> (Site, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let site = build_site(&dir, THEME, clock);
+    (site, dir)
+}
+
+/// Same as `test_site` but with a copy of the test theme in `<dir>/theme`,
+/// for tests changing the theme.
+#[allow(dead_code)]
+pub fn test_site_with_theme_copy() -> (Site, TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let theme = dir.path().join("theme");
+    copy_folder(Path::new(THEME), &theme);
+    let site = build_site(&dir, theme.to_str().unwrap(), Arc::new(SystemClock));
+    (site, dir)
+}
+
+/// Same as `test_site_with_theme_copy` with the article template declaring
+/// `fields`, written as under the `fields:` key, and rendered by `view` when
+/// one is given
+#[allow(dead_code)]
+pub fn site_with_article_fields(fields: &str, view: Option<&str>) -> (Site, TempDir) {
+    let (site, dir) = test_site_with_theme_copy();
+    let article = dir.path().join("theme/pages/article");
+    let fields: String = fields.lines().map(|line| format!("  {}\n", line)).collect();
+    fs::write(
+        article.join("index.yaml"),
+        format!("view: view.html\nfields:\n{}", fields),
+    )
+    .unwrap();
+    if let Some(view) = view {
+        fs::write(article.join("view.html"), view).unwrap();
+    }
+    site.reload_theme().unwrap();
+    (site, dir)
+}
+
+/// Write an empty storage file in `dir`, its pages kept in `<dir>/data`
+pub fn empty_storage(dir: &TempDir) -> PathBuf {
+    storage_with_pages(dir, &[], &[])
+}
+
+/// Write a storage file in `dir` listing `entries`, the yaml of each page
+/// in the `pages` map. The draft module file of every page is written in
+/// `<dir>/data`, next to the published one of the `published` pages.
+pub fn storage_with_pages(dir: &TempDir, entries: &[(&str, &str)], published: &[&str]) -> PathBuf {
+    let data = dir.path().join("data");
+    fs::create_dir(&data).unwrap();
+    let mut content = format!("---\nfolder: {}/\npages:", data.display());
+    if entries.is_empty() {
+        content.push_str(" {}");
+    }
+    let module = "template: /pages/article\nfields:\n  title: ''\nareas: {}\n";
+    for (id, entry) in entries {
+        content.push_str(&format!("\n  {}: {}", id, entry));
+        fs::write(data.join(format!("{}.yml", id)), module).unwrap();
+    }
+    for id in published {
+        fs::write(data.join(format!("{}.published.yml", id)), module).unwrap();
+    }
+    let storage = dir.path().join("yaml_storage.yml");
+    fs::write(&storage, content + "\n").unwrap();
+    storage
+}
+
+/// Build a site over the test theme and an existing storage file of `dir`
+#[allow(dead_code)]
+pub fn site_with_storage(dir: &TempDir, storage: &Path) -> Site {
+    open_site(dir, storage, THEME, Arc::new(SystemClock))
+}
+
+fn build_site(dir: &TempDir, theme: &str, clock: Arc<dyn Clock>) -> Site {
+    let storage = empty_storage(dir);
+    open_site(dir, &storage, theme, clock)
+}
+
+fn open_site(dir: &TempDir, storage: &Path, theme: &str, clock: Arc<dyn Clock>) -> Site {
+    SiteBuilder::new()
+        .add_tera_renderer()
+        .unwrap()
+        .add_yaml_storage(storage.to_str().unwrap())
+        .unwrap()
+        .add_theme(theme)
+        .unwrap()
+        .add_media(dir.path().join("media").to_str().unwrap())
+        .unwrap()
+        .add_clock(clock)
+        .build()
+        .unwrap()
+}
+
+fn copy_folder(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_folder(&entry.path(), &target);
+        } else {
+            fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
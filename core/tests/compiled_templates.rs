@@ -0,0 +1,120 @@
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use core::Site;
+
+mod common;
+
+const THREADS: usize = 8;
+const RENDERS: usize = 20;
+/// Renders of each thread in the benchmark
+const BENCH_RENDERS: usize = 200;
+
+#[test]
+fn renders_reuse_compiled_templates() {
+    let admin = common::admin();
+    let (site, dir) = common::test_site_with_theme_copy();
+    let id = site
+        .create_page(&admin, "first test", "/pages/article")
+        .unwrap();
+    site.publish_page(&admin, &id).unwrap();
+    let view = dir.path().join("theme/pages/article/view.html");
+    let html = site.render_page("first test").unwrap();
+
+    // Views are only read again when the theme is reloaded
+    std::fs::write(&view, "<h1>{{ title }}</h1>").unwrap();
+    assert_eq!(site.render_page("first test").unwrap(), html);
+    std::fs::remove_file(&view).unwrap();
+    assert_eq!(site.render_page("first test").unwrap(), html);
+
+    std::fs::write(&view, "<h1>{{ title }}</h1>").unwrap();
+    site.reload_theme().unwrap();
+    assert_eq!(site.render_page("first test").unwrap(), "<h1></h1>");
+}
+
+/// Publish a `first test` page holding a few components
+fn publish_sections(site: &Site) {
+    let admin = common::admin();
+    let id = site
+        .create_page(&admin, "first test", "/pages/section")
+        .unwrap();
+    for index in 0..10 {
+        site.insert_module(
+            &admin,
+            &id,
+            &format!("area_3/{}", index),
+            "/components/section",
+        )
+        .unwrap();
+    }
+    site.publish_page(&admin, &id).unwrap();
+}
+
+#[test]
+fn concurrent_renders() {
+    let (site, _dir) = common::test_site();
+    publish_sections(&site);
+    let expected = site.render_page("first test").unwrap();
+    let site = Arc::new(site);
+
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let site = Arc::clone(&site);
+            thread::spawn(move || {
+                (0..RENDERS)
+                    .map(|_| site.render_page("first test").unwrap())
+                    .collect::<Vec<String>>()
+            })
+        })
+        .collect();
+    for handle in handles {
+        assert!(handle.join().unwrap().iter().all(|html| *html == expected));
+    }
+}
+
+/// Render the page from every thread at once, returns renders per second
+fn render_throughput<F>(site: &Arc<Site>, render: F) -> f64
+where
+    F: Fn(&Site) + Send + Sync + Copy + 'static,
+{
+    let start = Instant::now();
+    let handles: Vec<_> = (0..THREADS)
+        .map(|_| {
+            let site = Arc::clone(site);
+            thread::spawn(move || (0..BENCH_RENDERS).for_each(|_| render(&site)))
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let elapsed = start.elapsed().max(Duration::from_micros(1));
+    (THREADS * BENCH_RENDERS) as f64 / elapsed.as_secs_f64()
+}
+
+/// Compare loading the theme for every render, as renders used to, with
+/// the compiled templates. Run with
+/// `cargo test --release --test compiled_templates -- --ignored --nocapture`
+#[test]
+#[ignore]
+fn benchmark_concurrent_renders() {
+    let (site, _dir) = common::test_site();
+    publish_sections(&site);
+    let site = Arc::new(site);
+
+    let loading = render_throughput(&site, |site| {
+        site.reload_theme().unwrap();
+        site.render_page("first test").unwrap();
+    });
+    let compiled = render_throughput(&site, |site| {
+        site.render_page("first test").unwrap();
+    });
+
+    println!(
+        "{} threads x {} renders: {:.0} renders/s loading the theme, {:.0} renders/s compiled",
+        THREADS, BENCH_RENDERS, loading, compiled
+    );
+}
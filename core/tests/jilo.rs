@@ -1,21 +1,1385 @@
-use core::{self, SiteBuilder};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Duration, Utc};
+use core::{
+    self, AccessEntry, Accounts, Area, AuditAction, AuditFilter, Change, Error, Field, PageStatus,
+    Right, Role, ScheduleAction, Severity, SiteBuilder, Subject, TemplateKind, User, UserChanges,
+    Value,
+};
+
+mod common;
 
 #[test]
 fn create_page() {
+    let admin = common::admin();
+    let dir = tempfile::tempdir().unwrap();
+    let storage = common::empty_storage(&dir);
     let site = SiteBuilder::new()
         .add_tera_renderer()
         .unwrap()
-        .add_yaml_storage("./tests/test_site/yaml_storage.yml")
+        .add_yaml_storage(storage.to_str().unwrap())
         .unwrap()
         .add_theme("./tests/test_site/theme")
         .unwrap()
-        .build();
+        .build()
+        .unwrap();
 
-    let id = site.create_page("first test", "/pages/section").unwrap();
+    let id = site
+        .create_page(&admin, "first test", "/pages/section")
+        .unwrap();
     let summary = site.summary();
 
     assert_eq!(summary.len(), 1);
     // assert_eq!(summary.get(&id).unwrap(), "first test");
 
-    site.delete_page(&id).unwrap();
+    site.delete_page(&admin, &id).unwrap();
+}
+
+#[test]
+fn build_site() {
+    let admin = common::admin();
+    let (site, dir) = common::test_site();
+    let first = site
+        .create_page(&admin, "first test", "/pages/section")
+        .unwrap();
+    let second = site
+        .create_page(&admin, "Second Test", "/pages/article")
+        .unwrap();
+    let home = site.create_page(&admin, "Home", "/pages/article").unwrap();
+    site.update_slug(&admin, &home, "index").unwrap();
+    site.create_page(&admin, "draft", "/pages/article").unwrap();
+    site.publish_page(&admin, &first).unwrap();
+    site.publish_page(&admin, &second).unwrap();
+    site.publish_page(&admin, &home).unwrap();
+
+    let output = dir.path().join("public");
+    let report = site.build(&output).unwrap();
+
+    assert!(report.is_success());
+    assert_eq!(report.pages.len(), 3);
+    assert!(output.join("first-test/index.html").is_file());
+    // The page served at the root of the site is the root of the export
+    assert_eq!(site.find_page("/").unwrap().id, home);
+    assert_eq!(
+        std::fs::read_to_string(output.join("index.html")).unwrap(),
+        site.render_page("Home").unwrap()
+    );
+    assert!(!output.join("index").exists());
+    assert!(!output.join("draft").exists());
+    let article = std::fs::read_to_string(output.join("second-test/index.html")).unwrap();
+    assert!(article.contains("<title></title>"));
+}
+
+#[test]
+fn rebuild_only_changed_pages() {
+    let admin = common::admin();
+    let (site, dir) = common::test_site_with_theme_copy();
+    let first = site
+        .create_page(&admin, "first test", "/pages/section")
+        .unwrap();
+    let second = site
+        .create_page(&admin, "second test", "/pages/article")
+        .unwrap();
+    site.publish_page(&admin, &first).unwrap();
+    site.publish_page(&admin, &second).unwrap();
+
+    let output = dir.path().join("public");
+    let report = site.build(&output).unwrap();
+    assert_eq!(report.pages.len(), 2);
+    assert!(report.skipped.is_empty());
+
+    let report = site.build(&output).unwrap();
+    assert!(report.pages.is_empty());
+    assert_eq!(report.skipped.len(), 2);
+
+    // A page whose content changed is rendered again
+    let mut fields = HashMap::new();
+    fields.insert("title".to_string(), Value::from("Changed"));
+    site.update_page(&admin, &second, fields).unwrap();
+    site.publish_page(&admin, &second).unwrap();
+    let report = site.build(&output).unwrap();
+    assert_eq!(report.pages.len(), 1);
+    assert_eq!(report.pages[0].name, "second test");
+    assert_eq!(report.skipped.len(), 1);
+
+    // A view is only taken into account once the theme is reloaded, as it is
+    // only compiled again then
+    let view = dir.path().join("theme/pages/article/view.html");
+    std::fs::write(&view, "<h1>{{ title }}</h1>").unwrap();
+    let report = site.build(&output).unwrap();
+    assert!(report.pages.is_empty());
+    site.reload_theme().unwrap();
+    let report = site.build(&output).unwrap();
+    assert_eq!(report.pages.len(), 1);
+    assert_eq!(report.pages[0].name, "second test");
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(
+        std::fs::read_to_string(output.join("second-test/index.html")).unwrap(),
+        "<h1>Changed</h1>"
+    );
+
+    site.delete_page(&admin, &first).unwrap();
+    let third = site
+        .create_page(&admin, "third test", "/pages/article")
+        .unwrap();
+    site.publish_page(&admin, &third).unwrap();
+    let report = site.build(&output).unwrap();
+
+    assert_eq!(report.pages.len(), 1);
+    assert_eq!(report.pages[0].name, "third test");
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.removed, vec![output.join("first-test")]);
+    assert!(!output.join("first-test").exists());
+}
+
+#[test]
+fn built_files_go_stale() {
+    let admin = common::admin();
+    let (site, dir) = common::test_site();
+    let id = site
+        .create_page(&admin, "first test", "/pages/article")
+        .unwrap();
+    site.publish_page(&admin, &id).unwrap();
+    assert_eq!(site.built_file(&id), None);
+
+    let output = dir.path().join("public");
+    site.build(&output).unwrap();
+    let built = output.join("first-test/index.html");
+    assert_eq!(site.built_file(&id), Some(built.clone()));
+
+    let mut fields = HashMap::new();
+    fields.insert("title".to_string(), Value::from("Draft only"));
+    site.update_page(&admin, &id, fields).unwrap();
+    assert_eq!(site.built_file(&id), Some(built.clone()));
+
+    site.publish_page(&admin, &id).unwrap();
+    assert_eq!(site.built_file(&id), None);
+    site.build(&output).unwrap();
+    assert_eq!(site.built_file(&id), Some(built));
+    site.update_slug(&admin, &id, "renamed").unwrap();
+    assert_eq!(site.built_file(&id), None);
+}
+
+#[test]
+fn build_output_collision() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = common::storage_with_pages(
+        &dir,
+        &[
+            (
+                "a",
+                "{name: Hello World, slug: hello-world, status: published}",
+            ),
+            (
+                "b",
+                "{name: hello world!, slug: hello-world, status: published}",
+            ),
+            ("c", "{name: Media, slug: media, status: published}"),
+            ("d", "{name: Contact, slug: contact, status: published}"),
+        ],
+        &["a", "b", "c", "d"],
+    );
+    let site = common::site_with_storage(&dir, &storage);
+
+    // Colliding pages fail, the others are still built
+    let output = dir.path().join("public");
+    let report = site.build(&output).unwrap();
+    let names: Vec<&str> = report.pages.iter().map(|page| page.name.as_str()).collect();
+    assert_eq!(names, vec!["Contact"]);
+    assert!(output.join("contact/index.html").is_file());
+
+    let mut failures: Vec<(&str, String, Vec<String>)> = report
+        .failures
+        .iter()
+        .map(|failure| match &failure.error {
+            Error::OutputCollision { path, pages } => {
+                (failure.id.as_str(), path.to_owned(), pages.to_owned())
+            }
+            other => panic!("unexpected error {:?}", other),
+        })
+        .collect();
+    failures.sort();
+    let colliding = vec!["Hello World".to_string(), "hello world!".to_string()];
+    assert_eq!(
+        failures,
+        vec![
+            ("a", "hello-world".to_string(), colliding.clone()),
+            ("b", "hello-world".to_string(), colliding),
+            ("c", "media".to_string(), vec!["Media".to_string()]),
+        ]
+    );
+    assert!(!output.join("hello-world").exists());
+    assert!(!output.join("media/index.html").exists());
+}
+
+#[test]
+fn load_baseline_storage() {
+    let admin = common::admin();
+    let dir = tempfile::tempdir().unwrap();
+    let storage = common::storage_with_pages(
+        &dir,
+        &[
+            ("a", "Hello World"),
+            ("b", "hello world!"),
+            ("c", "{name: Contact, slug: contact}"),
+        ],
+        &[],
+    );
+    let site = common::site_with_storage(&dir, &storage);
+
+    let mut pages = site.all_pages(&admin);
+    pages.sort_by(|a, b| a.id.cmp(&b.id));
+    let slugs: Vec<&str> = pages.iter().map(|page| page.slug.as_str()).collect();
+    assert_eq!(slugs, vec!["hello-world", "hello-world-2", "contact"]);
+    assert_eq!(pages[0].name, "Hello World");
+    assert_eq!(
+        site.page_content(&admin, "a").unwrap().module.template,
+        "/pages/article"
+    );
+
+    let converted = std::fs::read_to_string(&storage).unwrap();
+    assert!(converted.contains("slug: hello-world-2"));
+    let site = common::site_with_storage(&dir, &storage);
+    assert_eq!(site.all_pages(&admin).len(), 3);
+}
+
+#[test]
+fn pages_stored_before_publishing_stay_online() {
+    let admin = common::admin();
+    let dir = tempfile::tempdir().unwrap();
+    let storage = common::storage_with_pages(
+        &dir,
+        &[
+            ("a", "Hello World"),
+            ("b", "{name: Contact, slug: contact}"),
+            ("c", "{name: Draft, slug: draft, status: draft}"),
+        ],
+        &[],
+    );
+    let site = common::site_with_storage(&dir, &storage);
+
+    let status = |id: &str| site.page(&admin, id).unwrap().status;
+    assert_eq!(status("a"), PageStatus::Published);
+    assert_eq!(status("b"), PageStatus::Published);
+    assert_eq!(status("c"), PageStatus::Draft);
+    assert!(site.render_page("Hello World").is_ok());
+    assert!(site.render_page("Contact").is_ok());
+    assert!(matches!(
+        site.render_page("Draft"),
+        Err(Error::PageNotPublished)
+    ));
+    assert!(dir.path().join("data/b.published.yml").is_file());
+}
+
+#[test]
+fn update_page_fields() {
+    let admin = common::admin();
+    let (site, _dir) = common::test_site();
+    let id = site
+        .create_page(&admin, "first test", "/pages/article")
+        .unwrap();
+
+    let mut fields = HashMap::new();
+    fields.insert("title".to_string(), Value::from("Hello"));
+    let module = site.update_page(&admin, &id, fields).unwrap();
+    assert_eq!(module.fields["title"], Value::from("Hello"));
+
+    let html = site.preview_page(&admin, &id).unwrap();
+    assert!(html.contains("<title>Hello</title>"));
+
+    let mut fields = HashMap::new();
+    fields.insert("title".to_string(), Value::from(12));
+    fields.insert("subtitle".to_string(), Value::from("nope"));
+    match site.update_page(&admin, &id, fields) {
+        Err(Error::Validation(errors)) => assert_eq!(errors.len(), 2),
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    assert!(matches!(
+        site.update_page(&admin, "missing", HashMap::new()),
+        Err(Error::PageNotFound)
+    ));
+}
+
+#[test]
+fn edit_module_tree() {
+    let admin = common::admin();
+    let (site, _dir) = common::test_site();
+    let id = site
+        .create_page(&admin, "first test", "/pages/section")
+        .unwrap();
+    let other = site
+        .create_page(&admin, "second test", "/pages/section")
+        .unwrap();
+
+    site.insert_module(&admin, &id, "area_3/0", "/components/section")
+        .unwrap();
+    site.insert_module(&admin, &id, "area_3/0/content/0", "/components/image")
+        .unwrap();
+    let page = site
+        .insert_module(&admin, &id, "area_3/1", "/components/header")
+        .unwrap();
+    assert_eq!(page.areas["area_3"].len(), 2);
+    assert_eq!(page.areas["area_3"][0].areas["content"].len(), 1);
+
+    assert!(matches!(
+        site.insert_module(&admin, &id, "area_4/0", "/components/header"),
+        Err(Error::AreaNotFound)
+    ));
+    assert!(matches!(
+        site.insert_module(&admin, &id, "area_3/5", "/components/header"),
+        Err(Error::ModuleNotFound)
+    ));
+    assert!(matches!(
+        site.move_module(&admin, &id, "area_3/0", &id, "area_3/0/content/0"),
+        Err(Error::InvalidModulePath)
+    ));
+
+    let page = site
+        .reorder_modules(&admin, &id, "area_3", &[1, 0])
+        .unwrap();
+    assert_eq!(page.areas["area_3"][0].template, "/components/header");
+
+    let moved = site
+        .move_module(&admin, &id, "area_3/1/content/0", &other, "area_2/0")
+        .unwrap();
+    assert_eq!(moved.areas["area_2"][0].template, "/components/image");
+    let moved = site
+        .move_module(&admin, &id, "area_3/0", &id, "area_3/1")
+        .unwrap();
+    assert_eq!(moved.areas["area_3"][1].template, "/components/header");
+    let removed = site.remove_module(&admin, &id, "area_3/1").unwrap();
+    assert_eq!(removed.template, "/components/header");
+
+    let html = site.preview_page(&admin, &other).unwrap();
+    assert!(html.contains("<img"));
+    let html = site.preview_page(&admin, &id).unwrap();
+    assert!(html.contains("<section>"));
+    assert!(!html.contains("<nav>"));
+}
+
+#[test]
+fn enforce_area_rules() {
+    let admin = common::admin();
+    let (site, _dir) = common::test_site();
+    let id = site
+        .create_page(&admin, "first test", "/pages/section")
+        .unwrap();
+
+    site.insert_module(&admin, &id, "area_1/0", "/components/header")
+        .unwrap();
+    match site.insert_module(&admin, &id, "area_1/1", "/components/image") {
+        Err(Error::ComponentNotAccepted { area, template }) => {
+            assert_eq!(area, "area_1");
+            assert_eq!(template, "/components/image");
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert!(matches!(
+        site.move_module(&admin, &id, "area_1/0", &id, "area_2/0"),
+        Err(Error::ComponentNotAccepted { .. })
+    ));
+    assert!(matches!(
+        site.insert_module(&admin, &id, "area_3/0", "/pages/article"),
+        Err(Error::ComponentNotAccepted { .. })
+    ));
+
+    assert_eq!(
+        site.accepted_components(&admin, &id, "area_1").unwrap(),
+        vec!["/components/header"]
+    );
+    assert_eq!(
+        site.accepted_components(&admin, &id, "area_2").unwrap(),
+        vec!["/components/image", "/components/section"]
+    );
+}
+
+#[test]
+fn check_theme() {
+    assert!(core::check_theme("./tests/test_site/theme")
+        .unwrap()
+        .is_empty());
+
+    let dir = tempfile::tempdir().unwrap();
+    let write = |path: &str, content: &str| {
+        let path = dir.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    };
+    write(
+        "pages/home/index.yaml",
+        "view: view.html\nfields:\n  title: strng\n",
+    );
+    write("pages/list/index.yaml", "view: missing.html\n");
+    write(
+        "pages/blog/index.yaml",
+        "view: view.html\nfields:\n  title: string\nareas:\n  main:\n    accept: with\n    components:\n      - card\n",
+    );
+    write(
+        "pages/blog/view.html",
+        "<h1>{{ title }}</h1>\n{{ main | area }}\n<p>{{ subtitle }}</p>",
+    );
+
+    let diagnostics = core::check_theme(dir.path().to_str().unwrap()).unwrap();
+    let messages: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+    assert_eq!(diagnostics.len(), 4, "{:#?}", messages);
+
+    let blog = &diagnostics[0];
+    assert!(blog.path.ends_with("pages/blog/index.yaml"));
+    assert_eq!(blog.line, Some(8));
+    assert!(blog.message.contains("unknown component card"));
+
+    let view = &diagnostics[1];
+    assert!(view.path.ends_with("pages/blog/view.html"));
+    assert_eq!(view.line, Some(3));
+    assert!(view.message.contains("subtitle"));
+    assert_eq!(view.severity, Severity::Warning);
+
+    let home = &diagnostics[2];
+    assert!(home.path.ends_with("pages/home/index.yaml"));
+    assert_eq!(home.line, Some(3));
+    assert!(home.message.contains("unknown variant"));
+
+    let list = &diagnostics[3];
+    assert!(list.message.contains("missing.html does not exist"));
+
+    let built = SiteBuilder::new().add_theme(dir.path().to_str().unwrap());
+    assert!(matches!(built, Err(Error::InvalidTheme(d)) if d.len() == 4));
+
+    std::fs::remove_dir_all(dir.path().join("pages/home")).unwrap();
+    std::fs::remove_dir_all(dir.path().join("pages/list")).unwrap();
+    write(
+        "pages/blog/index.yaml",
+        "view: view.html\nfields:\n  title: string\nareas:\n  main:\n    accept: all\n",
+    );
+    let diagnostics = core::check_theme(dir.path().to_str().unwrap()).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert!(diagnostics[0].to_string().contains("warning: subtitle"));
+    assert!(SiteBuilder::new()
+        .add_theme(dir.path().to_str().unwrap())
+        .is_ok());
+}
+
+#[test]
+fn reload_theme() {
+    let admin = common::admin();
+    let (site, dir) = common::test_site_with_theme_copy();
+    let id = site
+        .create_page(&admin, "first test", "/pages/article")
+        .unwrap();
+    site.publish_page(&admin, &id).unwrap();
+    let article = dir.path().join("theme/pages/article");
+
+    std::fs::write(article.join("view.html"), "<h1>{{ title }}</h1>").unwrap();
+    site.reload_theme().unwrap();
+    assert_eq!(site.render_page("first test").unwrap(), "<h1></h1>");
+
+    std::fs::write(article.join("index.yaml"), "view: view.html\nfields: [").unwrap();
+    assert!(matches!(site.reload_theme(), Err(Error::InvalidTheme(..))));
+    assert_eq!(site.render_page("first test").unwrap(), "<h1></h1>");
+}
+
+#[test]
+fn render_rich_fields() {
+    let admin = common::admin();
+    let (site, _dir) = common::site_with_article_fields(
+        "title: string\nbody: markdown\naccent: color\nlayout:\n  select: [wide, narrow]",
+        Some("<main class=\"{{ layout }}\" style=\"color: {{ accent }}\">{{ body | safe }}</main>"),
+    );
+
+    let id = site
+        .create_page(&admin, "first test", "/pages/article")
+        .unwrap();
+    let content = site.page_content(&admin, &id).unwrap();
+    assert_eq!(content.module.fields["accent"], Value::from("#000000"));
+    assert_eq!(content.module.fields["layout"], Value::from("wide"));
+
+    let mut fields = HashMap::new();
+    fields.insert("accent".to_string(), Value::from("blue"));
+    fields.insert("layout".to_string(), Value::from("full"));
+    match site.update_page(&admin, &id, fields) {
+        Err(Error::Validation(errors)) => {
+            let names: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+            assert_eq!(names, vec!["accent", "layout"]);
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let mut fields = HashMap::new();
+    fields.insert(
+        "body".to_string(),
+        Value::from("# Hello\n\nSome *text*<script>alert(1)</script>"),
+    );
+    fields.insert("layout".to_string(), Value::from("narrow"));
+    site.update_page(&admin, &id, fields).unwrap();
+    site.publish_page(&admin, &id).unwrap();
+
+    assert_eq!(
+        site.render_page("first test").unwrap(),
+        "<main class=\"narrow\" style=\"color: #000000\"><h1>Hello</h1>\n<p>Some <em>text</em></p>\n</main>"
+    );
+}
+
+#[test]
+fn field_constraints() {
+    let admin = common::admin();
+    let (site, dir) = common::site_with_article_fields(
+        r#"title:
+  kind: string
+  required: true
+  max_length: 10
+  label: Title
+rating:
+  kind: number
+  min: 1
+  max: 5
+  default: 3
+code:
+  kind: string
+  pattern: "[A-Z]{3}"
+"#,
+        None,
+    );
+
+    let id = site
+        .create_page(&admin, "first test", "/pages/article")
+        .unwrap();
+    let content = site.page_content(&admin, &id).unwrap();
+    assert_eq!(content.module.fields["rating"], Value::from(3));
+    let title = &content.templates["/pages/article"].fields["title"];
+    assert!(title.required);
+    assert_eq!(title.label.as_deref(), Some("Title"));
+
+    // A draft can be saved incomplete but not published
+    match site.publish_page(&admin, &id) {
+        Err(Error::Validation(errors)) => {
+            assert_eq!(errors[0].to_string(), "title: field is required")
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    let due = Utc::now() - Duration::minutes(1);
+    site.schedule_page(&admin, &id, Some(due), None).unwrap();
+    assert!(site.apply_schedule().unwrap().is_empty());
+    assert_eq!(site.page(&admin, &id).unwrap().publish_at, Some(due));
+
+    let mut fields = HashMap::new();
+    fields.insert("rating".to_string(), Value::from(6));
+    fields.insert("code".to_string(), Value::from("abc"));
+    match site.update_page(&admin, &id, fields) {
+        Err(Error::Validation(errors)) => {
+            let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            assert_eq!(
+                messages,
+                vec![
+                    "code: does not match the expected format",
+                    "rating: must be at most 5",
+                    "title: field is required",
+                ]
+            );
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let mut fields = HashMap::new();
+    fields.insert("title".to_string(), Value::from("A long title"));
+    match site.update_page(&admin, &id, fields) {
+        Err(Error::Validation(errors)) => assert_eq!(
+            errors[0].to_string(),
+            "title: must be at most 10 characters long"
+        ),
+        other => panic!("unexpected result {:?}", other),
+    }
+
+    let mut fields = HashMap::new();
+    fields.insert("title".to_string(), Value::from("Hello"));
+    fields.insert("code".to_string(), Value::from("ABC"));
+    site.update_page(&admin, &id, fields).unwrap();
+    assert_eq!(site.apply_schedule().unwrap().len(), 1);
+    assert_eq!(
+        site.page(&admin, &id).unwrap().status,
+        PageStatus::Published
+    );
+
+    std::fs::write(
+        dir.path().join("theme/pages/article/index.yaml"),
+        "view: view.html\nfields:\n  rating:\n    kind: number\n    max: 5\n    default: 9\n",
+    )
+    .unwrap();
+    match site.reload_theme() {
+        Err(Error::InvalidTheme(diagnostics)) => {
+            assert!(diagnostics[0]
+                .message
+                .contains("default value of field rating"))
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn render_compound_fields() {
+    let admin = common::admin();
+    let (site, _dir) = common::site_with_article_fields(
+        r#"links:
+  list:
+    label: string
+    note: markdown
+author:
+  object:
+    name: string
+"#,
+        Some("{% for link in links %}<a>{{ link.label }}</a>{{ link.note | safe }}{% endfor %}<p>{{ author.name }}</p>"),
+    );
+
+    let id = site
+        .create_page(&admin, "first test", "/pages/article")
+        .unwrap();
+    let content = site.page_content(&admin, &id).unwrap();
+    assert_eq!(content.module.fields["links"], Value::Sequence(vec![]));
+    assert_eq!(content.module.fields["author"]["name"], Value::from(""));
+
+    let fields: HashMap<String, Value> = serde_yaml::from_str(
+        "links:\n  - label: One\n    note: '*first*'\n  - label: Two\n    note: ''\nauthor:\n  name: Jo\n",
+    )
+    .unwrap();
+    site.update_page(&admin, &id, fields).unwrap();
+    site.publish_page(&admin, &id).unwrap();
+
+    assert_eq!(
+        site.render_page("first test").unwrap(),
+        "<a>One</a><p><em>first</em></p>\n<a>Two</a><p>Jo</p>"
+    );
+}
+
+#[test]
+fn page_references() {
+    let admin = common::admin();
+    let (site, _dir) = common::site_with_article_fields(
+        r#"title: string
+related:
+  page_ref:
+    templates: [article]
+    fields: [title]
+"#,
+        Some("{% if related %}<a href=\"{{ related.url | safe }}\">{{ related.name }}: {{ related.fields.title }}</a>{% else %}none{% endif %}"),
+    );
+
+    let target = site
+        .create_page(&admin, "Target", "/pages/article")
+        .unwrap();
+    let mut fields = HashMap::new();
+    fields.insert("title".to_string(), Value::from("Read me"));
+    site.update_page(&admin, &target, fields).unwrap();
+    site.publish_page(&admin, &target).unwrap();
+    let section = site
+        .create_page(&admin, "Section", "/pages/section")
+        .unwrap();
+    let source = site
+        .create_page(&admin, "Source", "/pages/article")
+        .unwrap();
+
+    for (id, message) in [
+        ("missing", "page does not exist"),
+        (section.as_str(), "page template is not accepted"),
+    ] {
+        let mut fields = HashMap::new();
+        fields.insert("related".to_string(), Value::from(id));
+        match site.update_page(&admin, &source, fields) {
+            Err(Error::Validation(errors)) => {
+                assert_eq!(errors[0].to_string(), format!("related: {}", message))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    let mut fields = HashMap::new();
+    fields.insert("related".to_string(), Value::from(target.as_str()));
+    site.update_page(&admin, &source, fields).unwrap();
+    site.publish_page(&admin, &source).unwrap();
+    assert_eq!(
+        site.render_page("Source").unwrap(),
+        "<a href=\"/target/\">Target: Read me</a>"
+    );
+
+    let dangling = site.delete_page(&admin, &target).unwrap();
+    assert_eq!(dangling.len(), 1);
+    assert_eq!(dangling[0].page, source);
+    assert_eq!(dangling[0].module, None);
+    assert_eq!(dangling[0].field, "related");
+    assert_eq!(site.render_page("Source").unwrap(), "none");
+    match site.publish_page(&admin, &source) {
+        Err(Error::Validation(errors)) => {
+            assert_eq!(errors[0].to_string(), "related: page does not exist")
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert!(site.delete_page(&admin, &section).unwrap().is_empty());
+
+    // References written after the first lookup are followed too
+    let third = site.create_page(&admin, "Third", "/pages/article").unwrap();
+    let mut fields = HashMap::new();
+    fields.insert("related".to_string(), Value::from(source.as_str()));
+    site.update_page(&admin, &third, fields).unwrap();
+    let dangling = site.delete_page(&admin, &source).unwrap();
+    assert_eq!(dangling.len(), 1);
+    assert_eq!(dangling[0].page, third);
+}
+
+#[test]
+fn media_library() {
+    let admin = common::admin();
+    let (site, _dir) = common::site_with_article_fields(
+        "cover: image\nattachment: file",
+        Some("{% if cover %}<img src=\"{{ cover.url | safe }}\" alt=\"{{ cover.alt }}\" width=\"{{ cover.width }}\" />{% endif %}"),
+    );
+
+    let pixel = site
+        .upload_media(&admin, "pixel.png", PIXEL, "a dot")
+        .unwrap();
+    let notes = site
+        .upload_media(&admin, "notes.txt", b"some notes", "")
+        .unwrap();
+    assert!(pixel.is_image());
+    assert!(!notes.is_image());
+    assert!(matches!(
+        site.upload_media(&admin, "empty.png", b"", ""),
+        Err(Error::EmptyFile)
+    ));
+
+    let id = site
+        .create_page(&admin, "Gallery", "/pages/article")
+        .unwrap();
+    for (field, value, message) in [
+        ("cover", notes.id.as_str(), "media is not an image"),
+        ("attachment", "missing", "media does not exist"),
+    ] {
+        let mut fields = HashMap::new();
+        fields.insert(field.to_string(), Value::from(value));
+        match site.update_page(&admin, &id, fields) {
+            Err(Error::Validation(errors)) => {
+                assert_eq!(errors[0].to_string(), format!("{}: {}", field, message))
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    let mut fields = HashMap::new();
+    fields.insert("cover".to_string(), Value::from(pixel.id.as_str()));
+    fields.insert("attachment".to_string(), Value::from(notes.id.as_str()));
+    site.update_page(&admin, &id, fields).unwrap();
+    site.publish_page(&admin, &id).unwrap();
+    assert_eq!(
+        site.render_page("Gallery").unwrap(),
+        format!(
+            "<img src=\"/media/{}.png\" alt=\"a dot\" width=\"1\" />",
+            pixel.id
+        )
+    );
+
+    match site.delete_media(&admin, &pixel.id) {
+        Err(Error::MediaInUse(references)) => {
+            assert_eq!(references.len(), 1);
+            assert_eq!(references[0].page, id);
+            assert_eq!(references[0].field, "cover");
+        }
+        other => panic!("unexpected result {:?}", other),
+    }
+    let mut fields = HashMap::new();
+    fields.insert("cover".to_string(), Value::from(""));
+    site.update_page(&admin, &id, fields).unwrap();
+    site.publish_page(&admin, &id).unwrap();
+    site.delete_media(&admin, &pixel.id).unwrap();
+    assert!(matches!(site.media(&pixel.id), Err(Error::MediaNotFound)));
+    assert_eq!(site.media_list(&Default::default()).unwrap(), vec![notes]);
+}
+
+/// Smallest valid png, a single transparent pixel
+const PIXEL: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0x60, 0x00, 0x02, 0x00,
+    0x00, 0x05, 0x00, 0x01, 0x7a, 0x5e, 0xab, 0x3f, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44,
+    0xae, 0x42, 0x60, 0x82,
+];
+
+#[test]
+fn find_pages_by_url() {
+    let admin = common::admin();
+    let (site, _dir) = common::test_site();
+    let id = site
+        .create_page(&admin, "First Test", "/pages/article")
+        .unwrap();
+    site.create_page(&admin, "index", "/pages/article").unwrap();
+
+    assert_eq!(site.find_page("/first-test").unwrap().id, id);
+    assert_eq!(site.find_page("First Test/").unwrap().id, id);
+    assert_eq!(site.find_page("/").unwrap().name, "index");
+    assert!(site.find_page("/missing").is_none());
+
+    let html = site.render_not_found().unwrap();
+    assert!(html.contains("This page does not exist"));
+}
+
+#[test]
+fn page_slugs() {
+    let admin = common::admin();
+    let (site, _dir) = common::test_site();
+    let id = site
+        .create_page(&admin, "Première page", "/pages/article")
+        .unwrap();
+
+    let page = site.get_page_by_slug("premiere-page").unwrap();
+    assert_eq!(page.id, id);
+    assert_eq!(page.name, "Première page");
+
+    assert!(matches!(
+        site.create_page(&admin, "Premiere page", "/pages/article"),
+        Err(Error::DuplicatedSlug)
+    ));
+    assert!(matches!(
+        site.create_page(&admin, "???", "/pages/article"),
+        Err(Error::InvalidSlug)
+    ));
+
+    let other = site
+        .create_page(&admin, "second", "/pages/article")
+        .unwrap();
+    assert!(matches!(
+        site.update_slug(&admin, &other, "premiere-page"),
+        Err(Error::DuplicatedSlug)
+    ));
+    assert!(matches!(
+        site.update_slug(&admin, &other, "Not valid"),
+        Err(Error::InvalidSlug)
+    ));
+
+    let page = site.update_slug(&admin, &id, "accueil").unwrap();
+    assert_eq!(page.slug, "accueil");
+    assert_eq!(site.find_page("/accueil").unwrap().id, id);
+    assert!(site.get_page_by_slug("premiere-page").is_none());
+}
+
+#[test]
+fn page_tree() {
+    let admin = common::admin();
+    let (site, dir) = common::test_site();
+    let blog = site.create_page(&admin, "Blog", "/pages/article").unwrap();
+    let first = site
+        .create_page(&admin, "Post 1", "/pages/article")
+        .unwrap();
+    let second = site
+        .create_page(&admin, "Post 2", "/pages/article")
+        .unwrap();
+
+    for id in [&blog, &first, &second] {
+        site.publish_page(&admin, id).unwrap();
+    }
+    site.move_page(&admin, &first, Some(&blog), 0).unwrap();
+    let page = site.move_page(&admin, &second, Some(&blog), 0).unwrap();
+    assert_eq!(page.path, "blog/post-2");
+    assert_eq!(page.parent.as_deref(), Some(blog.as_str()));
+
+    let tree = site.tree(&admin);
+    assert_eq!(tree.len(), 1);
+    let children: Vec<&str> = tree[0]
+        .children
+        .iter()
+        .map(|node| node.page.id.as_str())
+        .collect();
+    assert_eq!(children, vec![second.as_str(), first.as_str()]);
+
+    assert!(matches!(
+        site.move_page(&admin, &blog, Some(&first), 0),
+        Err(Error::PageCycle)
+    ));
+    assert!(matches!(
+        site.move_page(&admin, &blog, Some(&blog), 0),
+        Err(Error::PageCycle)
+    ));
+    assert!(matches!(
+        site.delete_page(&admin, &blog),
+        Err(Error::PageHasChildren)
+    ));
+
+    assert_eq!(site.find_page("/blog/post-1").unwrap().id, first);
+    assert!(site.find_page("/post-1").is_none());
+
+    let output = dir.path().join("public");
+    let report = site.build(&output).unwrap();
+    assert!(report.is_success());
+    assert!(output.join("blog/post-1/index.html").is_file());
+    assert!(output.join("blog/index.html").is_file());
+
+    site.move_page(&admin, &first, None, 0).unwrap();
+    let report = site.build(&output).unwrap();
+    assert_eq!(report.removed, vec![output.join("blog/post-1")]);
+    assert!(output.join("post-1/index.html").is_file());
+    assert!(!output.join("blog/post-1").exists());
+    assert!(output.join("blog/post-2/index.html").is_file());
+    assert_eq!(site.tree(&admin)[0].page.id, first);
+}
+
+#[test]
+fn publish_pages() {
+    let admin = common::admin();
+    let (site, dir) = common::test_site();
+    let id = site
+        .create_page(&admin, "first test", "/pages/article")
+        .unwrap();
+    let page = site.get_page_by_slug("first-test").unwrap();
+    assert_eq!(page.status, PageStatus::Draft);
+    assert!(matches!(
+        site.render_page("first test"),
+        Err(Error::PageNotPublished)
+    ));
+
+    let mut fields = HashMap::new();
+    fields.insert("title".to_string(), Value::from("Published"));
+    site.update_page(&admin, &id, fields).unwrap();
+    let page = site.publish_page(&admin, &id).unwrap();
+    assert_eq!(page.status, PageStatus::Published);
+
+    let mut fields = HashMap::new();
+    fields.insert("title".to_string(), Value::from("Draft"));
+    site.update_page(&admin, &id, fields).unwrap();
+    assert_eq!(site.summary()[0].status, PageStatus::Modified);
+
+    let html = site.render_page("first test").unwrap();
+    assert!(html.contains("<title>Published</title>"));
+    let html = site.preview_page(&admin, &id).unwrap();
+    assert!(html.contains("<title>Draft</title>"));
+
+    let output = dir.path().join("public");
+    site.build(&output).unwrap();
+    let built = std::fs::read_to_string(output.join("first-test/index.html")).unwrap();
+    assert!(built.contains("<title>Published</title>"));
+
+    let page = site.unpublish_page(&admin, &id).unwrap();
+    assert_eq!(page.status, PageStatus::Draft);
+    let report = site.build(&output).unwrap();
+    assert_eq!(report.removed, vec![output.join("first-test")]);
+    assert!(site.preview_page(&admin, &id).is_ok());
+}
+
+#[test]
+fn page_revisions() {
+    let admin = common::admin();
+    let start: DateTime<Utc> = "2024-01-01T08:00:00Z".parse().unwrap();
+    let clock = Arc::new(TestClock(Mutex::new(start)));
+    let (site, _dir) = common::test_site_with_clock(clock.clone());
+    let id = site
+        .create_page(&admin, "first test", "/pages/section")
+        .unwrap();
+    clock.set(start + Duration::hours(1));
+    site.insert_module(&admin, &id, "area_3/0", "/components/header")
+        .unwrap();
+    site.insert_module(&admin, &id, "area_3/1", "/components/image")
+        .unwrap();
+    site.reorder_modules(&admin, &id, "area_3", &[1, 0])
+        .unwrap();
+
+    let revisions = site.revisions(&admin, &id).unwrap();
+    let numbers: Vec<usize> = revisions.iter().map(|r| r.number).collect();
+    assert_eq!(numbers, vec![1, 2, 3, 4]);
+    assert_eq!(revisions[0].author.as_deref(), Some("admin"));
+    // Revisions are dated by the clock of the site
+    assert_eq!(revisions[0].date, start);
+    assert_eq!(revisions[3].date, start + Duration::hours(1));
+
+    let changes = site.diff_revisions(&admin, &id, 1, 3).unwrap();
+    assert_eq!(changes.len(), 2);
+    assert!(changes
+        .iter()
+        .all(|change| matches!(change, Change::ModuleAdded { .. })));
+    let changes = site.diff_revisions(&admin, &id, 3, 4).unwrap();
+    assert!(matches!(
+        changes.as_slice(),
+        [Change::ModuleMoved { template, .. }] if template == "/components/header"
+            || template == "/components/image"
+    ));
+
+    let restored = site.restore_revision(&admin, &id, 2).unwrap();
+    assert_eq!(restored.areas["area_3"].len(), 1);
+    let revisions = site.revisions(&admin, &id).unwrap();
+    assert_eq!(revisions.len(), 5);
+    assert_eq!(revisions[4].module, restored);
+    assert!(site.diff_revisions(&admin, &id, 2, 5).unwrap().is_empty());
+
+    assert!(matches!(
+        site.restore_revision(&admin, &id, 12),
+        Err(Error::RevisionNotFound)
+    ));
+}
+
+/// A clock tests move forward by hand
+#[derive(Debug)]
+struct TestClock(Mutex<DateTime<Utc>>);
+
+impl TestClock {
+    fn set(&self, now: DateTime<Utc>) {
+        *self.0.lock().unwrap() = now;
+    }
+}
+
+impl core::Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[test]
+fn scheduled_publishing() {
+    let admin = common::admin();
+    let start: DateTime<Utc> = "2024-01-01T08:00:00Z".parse().unwrap();
+    let clock = Arc::new(TestClock(Mutex::new(start)));
+    let (site, dir) = common::test_site_with_clock(clock.clone());
+    let id = site
+        .create_page(&admin, "first test", "/pages/article")
+        .unwrap();
+    let other = site
+        .create_page(&admin, "second test", "/pages/article")
+        .unwrap();
+
+    let publish_at = start + Duration::hours(1);
+    let unpublish_at = start + Duration::hours(2);
+    assert!(matches!(
+        site.schedule_page(&admin, &id, Some(unpublish_at), Some(publish_at)),
+        Err(Error::InvalidSchedule)
+    ));
+    site.schedule_page(&admin, &id, Some(publish_at), Some(unpublish_at))
+        .unwrap();
+    site.publish_page(&admin, &other).unwrap();
+    site.schedule_page(&admin, &other, None, Some(publish_at))
+        .unwrap();
+
+    let upcoming = site.upcoming_changes(&admin);
+    let planned: Vec<(&str, ScheduleAction)> = upcoming
+        .iter()
+        .map(|change| (change.id.as_str(), change.action))
+        .collect();
+    assert_eq!(
+        planned,
+        vec![
+            (id.as_str(), ScheduleAction::Publish),
+            (other.as_str(), ScheduleAction::Unpublish),
+            (id.as_str(), ScheduleAction::Unpublish),
+        ]
+    );
+    assert!(site.apply_schedule().unwrap().is_empty());
+    assert_eq!(site.summary().len(), 1);
+    assert_eq!(site.all_pages(&admin).len(), 2);
+
+    clock.set(publish_at);
+    let applied = site.apply_schedule().unwrap();
+    assert_eq!(applied.len(), 2);
+    let statuses: HashMap<String, PageStatus> = site
+        .summary()
+        .into_iter()
+        .map(|page| (page.id, page.status))
+        .collect();
+    assert_eq!(statuses[&id], PageStatus::Published);
+    assert_eq!(statuses[&other], PageStatus::Draft);
+    assert!(site.render_page("first test").is_ok());
+    assert!(matches!(
+        site.render_page("second test"),
+        Err(Error::PageNotPublished)
+    ));
+
+    let output = dir.path().join("public");
+    site.build(&output).unwrap();
+    assert!(output.join("first-test/index.html").is_file());
+    assert!(!output.join("second-test").exists());
+
+    // Past its window the page is hidden even before the schedule is applied
+    clock.set(unpublish_at);
+    assert!(matches!(
+        site.render_page("first test"),
+        Err(Error::PageNotPublished)
+    ));
+    site.apply_schedule().unwrap();
+    assert!(site.upcoming_changes(&admin).is_empty());
+    assert!(site
+        .all_pages(&admin)
+        .iter()
+        .all(|page| page.status == PageStatus::Draft));
+    let report = site.build(&output).unwrap();
+    assert_eq!(report.removed, vec![output.join("first-test")]);
+}
+
+#[test]
+fn page_access() {
+    let admin = common::admin();
+    let (site, _dir) = common::test_site();
+    let blog = site.create_page(&admin, "Blog", "/pages/article").unwrap();
+    let post = site.create_page(&admin, "Post", "/pages/article").unwrap();
+    let about = site.create_page(&admin, "About", "/pages/article").unwrap();
+    site.move_page(&admin, &post, Some(&blog), 0).unwrap();
+
+    let editor = |name: &str, groups: &[&str]| User {
+        name: name.into(),
+        role: Role::Editor,
+        groups: groups.iter().map(|group| group.to_string()).collect(),
+    };
+    let writer = editor("writer", &["bloggers"]);
+    let other = editor("other", &[]);
+
+    assert!(matches!(
+        site.set_access(&writer, &blog, vec![]),
+        Err(Error::Forbidden)
+    ));
+    site.set_access(
+        &admin,
+        &blog,
+        vec![
+            AccessEntry {
+                subject: Subject::Group("bloggers".into()),
+                rights: vec![Right::View, Right::Edit],
+            },
+            AccessEntry {
+                subject: Subject::User("other".into()),
+                rights: vec![Right::View],
+            },
+        ],
+    )
+    .unwrap();
+
+    // Children inherit the entries of their closest restricted ancestor
+    let mut fields = HashMap::new();
+    fields.insert("title".to_string(), Value::from("Hello"));
+    site.update_page(&writer, &post, fields.clone()).unwrap();
+    assert!(matches!(
+        site.update_page(&other, &post, fields.clone()),
+        Err(Error::Forbidden)
+    ));
+    assert!(matches!(
+        site.publish_page(&writer, &post),
+        Err(Error::Forbidden)
+    ));
+    assert!(matches!(
+        site.delete_page(&writer, &post),
+        Err(Error::Forbidden)
+    ));
+    site.publish_page(&admin, &post).unwrap();
+
+    // Moving a page out of its restricted ancestors is left to admins
+    assert!(matches!(
+        site.move_page(&writer, &post, None, 0),
+        Err(Error::Forbidden)
+    ));
+    assert!(matches!(
+        site.move_page(&writer, &post, Some(&about), 0),
+        Err(Error::Forbidden)
+    ));
+    site.move_page(&writer, &about, None, 0).unwrap();
+
+    // Pages without entries up to the root stay open
+    site.update_page(&other, &about, fields).unwrap();
+
+    let viewer = editor("viewer", &[]);
+    let names: Vec<String> = site
+        .all_pages(&viewer)
+        .into_iter()
+        .map(|page| page.name)
+        .collect();
+    assert_eq!(names, vec!["About".to_string()]);
+    assert!(matches!(
+        site.revisions(&viewer, &post),
+        Err(Error::Forbidden)
+    ));
+    assert_eq!(site.all_pages(&other).len(), 3);
+    assert_eq!(
+        site.revisions(&writer, &post).unwrap()[1].author.as_deref(),
+        Some("writer")
+    );
+
+    // Changing a media takes the edit right on the whole site, deleting it
+    // the delete right
+    let notes = site
+        .upload_media(&admin, "notes.txt", b"some notes", "")
+        .unwrap();
+    assert!(matches!(
+        site.update_media(&other, &notes.id, "Notes"),
+        Err(Error::Forbidden)
+    ));
+    assert_eq!(site.media(&notes.id).unwrap().alt, "");
+    site.update_media(&admin, &notes.id, "Notes").unwrap();
+    assert!(matches!(
+        site.delete_media(&writer, &notes.id),
+        Err(Error::Forbidden)
+    ));
+    site.delete_media(&admin, &notes.id).unwrap();
+}
+
+#[test]
+fn audit_log() {
+    let admin = common::admin();
+    let start: DateTime<Utc> = "2024-01-01T08:00:00Z".parse().unwrap();
+    let clock = Arc::new(TestClock(Mutex::new(start)));
+    let (site, dir) = common::test_site_with_clock(clock.clone());
+    let accounts = Accounts::load(dir.path().join("users.yml").to_str().unwrap()).unwrap();
+    let editor = User {
+        name: "editor".into(),
+        role: Role::Editor,
+        groups: vec![],
+    };
+
+    let id = site
+        .create_page(&editor, "first test", "/pages/section")
+        .unwrap();
+    clock.set(start + Duration::hours(1));
+    site.insert_module(&editor, &id, "area_3/0", "/components/header")
+        .unwrap();
+    site.update_slug(&admin, &id, "first").unwrap();
+    site.schedule_page(&admin, &id, Some(start + Duration::hours(2)), None)
+        .unwrap();
+    clock.set(start + Duration::hours(3));
+    site.apply_schedule().unwrap();
+    site.delete_page(&admin, &id).unwrap();
+    site.create_user(&accounts, &admin, "writer", "secret", Role::Editor)
+        .unwrap();
+    assert!(matches!(
+        site.delete_user(&accounts, &editor, "writer"),
+        Err(Error::Forbidden)
+    ));
+    let changes = UserChanges {
+        groups: Some(vec!["news".into()]),
+        ..UserChanges::default()
+    };
+    site.update_user(&accounts, &admin, "writer", changes)
+        .unwrap();
+    site.delete_user(&accounts, &admin, "writer").unwrap();
+
+    let actions: Vec<AuditAction> = site
+        .audit_log(&admin, &AuditFilter::default())
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.action)
+        .collect();
+    assert_eq!(
+        actions,
+        vec![
+            AuditAction::PageCreated,
+            AuditAction::PageEdited,
+            AuditAction::SlugChanged,
+            AuditAction::PageScheduled,
+            AuditAction::PagePublished,
+            AuditAction::PageDeleted,
+            AuditAction::UserCreated,
+            AuditAction::UserUpdated,
+            AuditAction::UserDeleted,
+        ]
+    );
+
+    let filter = AuditFilter {
+        user: Some("editor".into()),
+        page: Some(id.to_owned()),
+        from: Some(start + Duration::minutes(30)),
+        ..AuditFilter::default()
+    };
+    let entries = site.audit_log(&admin, &filter).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].action, AuditAction::PageEdited);
+    assert_eq!(entries[0].date, start + Duration::hours(1));
+    assert!(matches!(
+        entries[0].changes.as_slice(),
+        [Change::ModuleAdded { .. }]
+    ));
+
+    let filter = AuditFilter {
+        action: Some(AuditAction::UserUpdated),
+        ..AuditFilter::default()
+    };
+    let entries = site.audit_log(&admin, &filter).unwrap();
+    assert_eq!(entries[0].before.as_deref(), Some("editor, groups: "));
+    assert_eq!(entries[0].after.as_deref(), Some("editor, groups: news"));
+
+    let filter = AuditFilter {
+        action: Some(AuditAction::SlugChanged),
+        ..AuditFilter::default()
+    };
+    let entries = site.audit_log(&admin, &filter).unwrap();
+    assert_eq!(entries[0].before.as_deref(), Some("first-test"));
+    assert_eq!(entries[0].after.as_deref(), Some("first"));
+
+    let filter = AuditFilter {
+        action: Some(AuditAction::PagePublished),
+        ..AuditFilter::default()
+    };
+    assert_eq!(site.audit_log(&admin, &filter).unwrap()[0].user, None);
+    assert!(matches!(
+        site.audit_log(&editor, &AuditFilter::default()),
+        Err(Error::Forbidden)
+    ));
+}
+
+#[test]
+fn page_content() {
+    let admin = common::admin();
+    let (site, _dir) = common::test_site();
+    let id = site
+        .create_page(&admin, "first test", "/pages/section")
+        .unwrap();
+    site.insert_module(&admin, &id, "area_1/0", "/components/header")
+        .unwrap();
+
+    let content = site.page_content(&admin, &id).unwrap();
+    assert_eq!(content.page.name, "first test");
+    assert_eq!(
+        content.module.areas["area_1"][0].template,
+        "/components/header"
+    );
+    let names: Vec<&String> = content.templates.keys().collect();
+    assert_eq!(names, vec!["/components/header", "/pages/section"]);
+
+    let section = &content.templates["/pages/section"];
+    assert_eq!(section.fields["field_1"].kind, Field::String);
+    assert_eq!(
+        section.areas["area_1"].accepted,
+        vec!["/components/header".to_string()]
+    );
+    assert!(!section.areas["area_2"]
+        .accepted
+        .contains(&"/components/header".to_string()));
+
+    assert!(matches!(
+        site.page_content(&admin, "missing"),
+        Err(Error::PageNotFound)
+    ));
+}
+
+#[test]
+fn theme_templates() {
+    let (site, _dir) = common::test_site();
+    let templates = site.templates().unwrap();
+    let pages: Vec<&String> = templates
+        .iter()
+        .filter(|(_, template)| template.kind == TemplateKind::Page)
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(pages, vec!["/pages/article", "/pages/section"]);
+    assert!(matches!(
+        site.create_page(&common::admin(), "Lost", "/pages/not_found"),
+        Err(Error::TemplateNotFound)
+    ));
+
+    let section = site.template("/components/section").unwrap();
+    assert_eq!(section.kind, TemplateKind::Component);
+    assert_eq!(section.fields["title"].kind, Field::String);
+    assert_eq!(section.areas["content"].rule, Area::All);
+    assert!(section.areas["content"]
+        .accepted
+        .iter()
+        .all(|name| name.starts_with("/components/")));
+
+    let page = site.template("/pages/section").unwrap();
+    assert_eq!(
+        page.areas["area_1"].rule,
+        Area::With(vec!["header".to_string()])
+    );
+    assert!(matches!(
+        site.template("/pages/missing"),
+        Err(Error::TemplateNotFound)
+    ));
 }
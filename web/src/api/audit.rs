@@ -0,0 +1,25 @@
+use core::{AuditFilter, Site};
+
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    auth::{Admin, Signed},
+    error::ApiError,
+};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let audit = web::resource("/audit").route(web::get().to(show_audit));
+
+    cfg.service(audit);
+}
+
+/// Entries of the audit log, filtered by `user`, `page`, `action`, `from`
+/// and `to` query parameters
+async fn show_audit(
+    signed: Signed<Admin>,
+    filter: web::Query<AuditFilter>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let entries = site.audit_log(&signed.user, &filter)?;
+    Ok(HttpResponse::Ok().json(entries))
+}
@@ -0,0 +1,72 @@
+use core::Site;
+use std::path::Path;
+
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+
+use crate::{
+    auth::{Editor, Signed},
+    error::ApiError,
+    OUTPUT_DIR,
+};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let build = web::resource("/build").route(web::post().to(build_site));
+
+    cfg.service(build);
+}
+
+#[derive(Debug, Serialize)]
+struct BuildResponse {
+    pages: Vec<BuiltPage>,
+    skipped: Vec<BuiltPage>,
+    removed: Vec<String>,
+    failures: Vec<BuildFailure>,
+}
+
+#[derive(Debug, Serialize)]
+struct BuiltPage {
+    id: String,
+    name: String,
+    path: String,
+}
+
+impl From<core::BuiltPage> for BuiltPage {
+    fn from(page: core::BuiltPage) -> Self {
+        Self {
+            id: page.id,
+            name: page.name,
+            path: page.path.to_string_lossy().to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct BuildFailure {
+    id: String,
+    name: String,
+    error: String,
+}
+
+async fn build_site(_: Signed<Editor>, site: web::Data<Site>) -> Result<HttpResponse, ApiError> {
+    let report = site.build(Path::new(OUTPUT_DIR))?;
+    let response = BuildResponse {
+        pages: report.pages.into_iter().map(BuiltPage::from).collect(),
+        skipped: report.skipped.into_iter().map(BuiltPage::from).collect(),
+        removed: report
+            .removed
+            .iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect(),
+        failures: report
+            .failures
+            .into_iter()
+            .map(|failure| BuildFailure {
+                id: failure.id,
+                name: failure.name,
+                error: failure.error.to_string(),
+            })
+            .collect(),
+    };
+    Ok(HttpResponse::Ok().json(response))
+}
@@ -0,0 +1,145 @@
+use core::{MediaFilter, Site};
+
+use actix_multipart::Multipart;
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use futures_util::TryStreamExt;
+use serde::Deserialize;
+
+use crate::{
+    auth::{Editor, Signed},
+    error::{invalid_request, too_large, ApiError},
+};
+
+/// Largest file the library accepts, in bytes
+const MAX_UPLOAD_SIZE: usize = 20 * 1024 * 1024;
+/// Largest upload request, the file with room for the alt text and the
+/// multipart boundaries
+const MAX_REQUEST_SIZE: usize = MAX_UPLOAD_SIZE + 64 * 1024;
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let media = web::resource("/media")
+        .route(web::get().to(list_media))
+        .route(web::post().to(upload_media));
+    let media_id = web::resource("/media/{id}")
+        .route(web::get().to(show_media))
+        .route(web::patch().to(update_media))
+        .route(web::delete().to(delete_media));
+
+    cfg.service(media);
+    cfg.service(media_id);
+}
+
+/// Media of the library meeting the query, most recent first
+async fn list_media(
+    _: Signed<Editor>,
+    filter: web::Query<MediaFilter>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let media = site.media_list(&filter)?;
+    Ok(HttpResponse::Ok().json(media))
+}
+
+/// A multipart form with the uploaded `file` and an optional `alt` text,
+/// each part at most once
+async fn upload_media(
+    signed: Signed<Editor>,
+    req: HttpRequest,
+    mut payload: Multipart,
+    site: web::Data<Site>,
+) -> actix_web::Result<HttpResponse> {
+    let announced = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<usize>().ok());
+    if announced.is_some_and(|length| length > MAX_REQUEST_SIZE) {
+        return Err(too_large(MAX_REQUEST_SIZE));
+    }
+
+    let mut file = None;
+    let mut alt = None;
+    let mut received = 0;
+    while let Some(mut field) = payload
+        .try_next()
+        .await
+        .map_err(|error| invalid_request(error, &req))?
+    {
+        let name = field.name().unwrap_or_default().to_owned();
+        let repeated = match name.as_str() {
+            "file" => file.is_some(),
+            "alt" => alt.is_some(),
+            _ => return Err(invalid_request(format!("unexpected part {}", name), &req)),
+        };
+        if repeated {
+            return Err(invalid_request(format!("part {} is repeated", name), &req));
+        }
+        let file_name = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .unwrap_or_default()
+            .to_owned();
+        let mut content = Vec::new();
+        while let Some(chunk) = field
+            .try_next()
+            .await
+            .map_err(|error| invalid_request(error, &req))?
+        {
+            received += chunk.len();
+            if received > MAX_REQUEST_SIZE {
+                return Err(too_large(MAX_REQUEST_SIZE));
+            }
+            if content.len() + chunk.len() > MAX_UPLOAD_SIZE {
+                return Err(too_large(MAX_UPLOAD_SIZE));
+            }
+            content.extend_from_slice(&chunk);
+        }
+        match name.as_str() {
+            "file" => file = Some((file_name, content)),
+            _ => alt = Some(String::from_utf8_lossy(&content).into_owned()),
+        }
+    }
+    let alt = alt.unwrap_or_default();
+    let (name, content) = file.ok_or_else(|| invalid_request("missing file part", &req))?;
+
+    let media = site
+        .upload_media(&signed.user, &name, &content, &alt)
+        .map_err(ApiError::from)?;
+    Ok(HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/api/v1/media/{}", media.id)))
+        .json(media))
+}
+
+async fn show_media(
+    _: Signed<Editor>,
+    id: web::Path<String>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let media = site.media(&id)?;
+    Ok(HttpResponse::Ok().json(media))
+}
+
+#[derive(Debug, Deserialize)]
+struct UpdateMediaBody {
+    alt: String,
+}
+
+async fn update_media(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    body: web::Json<UpdateMediaBody>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let media = site.update_media(&signed.user, &id, &body.alt)?;
+    Ok(HttpResponse::Ok().json(media))
+}
+
+/// Refused while a page or a module still uses the media, or to users not
+/// allowed to delete pages everywhere
+async fn delete_media(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    site.delete_media(&signed.user, &id)?;
+    Ok(HttpResponse::NoContent().finish())
+}
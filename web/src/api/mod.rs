@@ -1,11 +1,36 @@
+mod audit;
+mod build;
+mod media;
+mod module;
 mod page;
+mod revision;
+mod schedule;
+mod template;
+mod theme;
+mod user;
 
 use actix_web::web;
 
+use crate::error::invalid_request;
+
 pub fn config(cfg: &mut web::ServiceConfig) {
-    let v1 = web::scope("/v1").configure(page::config);
+    let v1 = web::scope("/v1")
+        .configure(page::config)
+        .configure(module::config)
+        .configure(revision::config)
+        .configure(schedule::config)
+        .configure(build::config)
+        .configure(template::config)
+        .configure(theme::config)
+        .configure(user::config)
+        .configure(media::config)
+        .configure(audit::config);
 
-    let api_v1_scope = web::scope("/api").service(v1);
+    let api_v1_scope = web::scope("/api")
+        .app_data(web::JsonConfig::default().error_handler(invalid_request))
+        .app_data(web::QueryConfig::default().error_handler(invalid_request))
+        .app_data(web::PathConfig::default().error_handler(invalid_request))
+        .service(v1);
 
     cfg.service(api_v1_scope);
 }
@@ -0,0 +1,97 @@
+use core::Site;
+
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+use crate::{
+    auth::{Editor, Signed},
+    error::ApiError,
+};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let modules = web::resource("/pages/{id}/modules").route(web::post().to(insert_module));
+    let modules_move = web::resource("/pages/{id}/modules/move").route(web::post().to(move_module));
+    let modules_reorder =
+        web::resource("/pages/{id}/modules/reorder").route(web::post().to(reorder_modules));
+    let areas = web::resource("/pages/{id}/areas/{area:.*}").route(web::get().to(show_area));
+    let modules_path =
+        web::resource("/pages/{id}/modules/{path:.*}").route(web::delete().to(remove_module));
+
+    cfg.service(modules);
+    cfg.service(modules_move);
+    cfg.service(modules_reorder);
+    cfg.service(modules_path);
+    cfg.service(areas);
+}
+
+#[derive(Debug, Deserialize)]
+struct InsertData {
+    path: String,
+    template: String,
+}
+
+async fn insert_module(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    form: web::Json<InsertData>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let page = site.insert_module(&signed.user, &id, &form.path, &form.template)?;
+    Ok(HttpResponse::Created().json(page))
+}
+
+#[derive(Debug, Deserialize)]
+struct MoveData {
+    from: String,
+    to: String,
+    /// Destination page, the same page when missing
+    page: Option<String>,
+}
+
+/// Gives back the draft of the destination page
+async fn move_module(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    form: web::Json<MoveData>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let to_page = form.page.as_deref().unwrap_or(&id);
+    let page = site.move_module(&signed.user, &id, &form.from, to_page, &form.to)?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+#[derive(Debug, Deserialize)]
+struct ReorderData {
+    area: String,
+    order: Vec<usize>,
+}
+
+async fn reorder_modules(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    form: web::Json<ReorderData>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let page = site.reorder_modules(&signed.user, &id, &form.area, &form.order)?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+async fn remove_module(
+    signed: Signed<Editor>,
+    path: web::Path<(String, String)>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, path) = path.into_inner();
+    let module = site.remove_module(&signed.user, &id, &path)?;
+    Ok(HttpResponse::Ok().json(module))
+}
+
+async fn show_area(
+    signed: Signed<Editor>,
+    path: web::Path<(String, String)>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, area) = path.into_inner();
+    let components = site.accepted_components(&signed.user, &id, &area)?;
+    Ok(HttpResponse::Ok().json(components))
+}
@@ -1,24 +1,61 @@
-use core::Site;
+use core::{
+    AccessEntry, FieldDefinition, Module, Page, Reference, Site, TemplateDefinition, Value,
+};
+use std::collections::{BTreeMap, HashMap};
 
-use actix_web::{http::Method, web, HttpResponse};
-use serde::Deserialize;
+use actix_web::{
+    http::{
+        header::{self, ContentType},
+        Method,
+    },
+    web, HttpResponse,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::{Admin, Editor, Signed},
+    error::ApiError,
+};
 
 pub fn config(cfg: &mut web::ServiceConfig) {
     let pages = web::resource("/pages")
         .route(web::get().to(show_all_pages))
         .route(web::post().to(create_page))
         .route(web::method(Method::OPTIONS).to(get_pages_options));
-    let pages_id = web::resource("/pages/{id}").route(web::method(Method::DELETE).to(delete_page));
+    let pages_tree = web::resource("/pages/tree").route(web::get().to(show_tree));
+    let pages_id = web::resource("/pages/{id}")
+        .route(web::get().to(show_page))
+        .route(web::patch().to(update_page))
+        .route(web::method(Method::DELETE).to(delete_page));
+    let pages_slug = web::resource("/pages/{id}/slug").route(web::put().to(update_slug));
+    let pages_parent = web::resource("/pages/{id}/parent").route(web::put().to(move_page));
+    let pages_publish = web::resource("/pages/{id}/publish")
+        .route(web::post().to(publish_page))
+        .route(web::method(Method::DELETE).to(unpublish_page));
+    let pages_access = web::resource("/pages/{id}/access").route(web::put().to(set_access));
+    let pages_preview = web::resource("/pages/{id}/preview").route(web::get().to(preview_page));
+    let slugs = web::resource("/slugs/{slug}").route(web::get().to(show_page_by_slug));
 
     cfg.service(pages);
+    cfg.service(pages_tree);
     cfg.service(pages_id);
+    cfg.service(pages_slug);
+    cfg.service(pages_parent);
+    cfg.service(pages_publish);
+    cfg.service(pages_access);
+    cfg.service(pages_preview);
+    cfg.service(slugs);
 }
 
-async fn show_all_pages(site: web::Data<Site>) -> HttpResponse {
-    let summary = site.summary();
+async fn show_all_pages(signed: Signed<Editor>, site: web::Data<Site>) -> HttpResponse {
+    let summary = site.all_pages(&signed.user);
     HttpResponse::Ok().json(summary)
 }
 
+async fn show_tree(signed: Signed<Editor>, site: web::Data<Site>) -> HttpResponse {
+    HttpResponse::Ok().json(site.tree(&signed.user))
+}
+
 async fn get_pages_options() -> HttpResponse {
     HttpResponse::Ok().json("response")
 }
@@ -29,21 +66,210 @@ struct PageData {
     template: String,
 }
 
-async fn create_page(form: web::Json<PageData>, site: web::Data<Site>) -> HttpResponse {
-    match site.create_page(&form.name, &form.template) {
-        Ok(id) => HttpResponse::Created().json(id),
-        Err(error) => match error {
-            core::Error::DuplicatedName | core::Error::EmptyPageName => {
-                HttpResponse::BadRequest().json(error.to_string())
-            }
-            _ => HttpResponse::InternalServerError().json(error.to_string()),
-        },
+async fn create_page(
+    signed: Signed<Editor>,
+    form: web::Json<PageData>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let id = site.create_page(&signed.user, &form.name, &form.template)?;
+    let content = site.page_content(&signed.user, &id)?;
+    Ok(HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/api/v1/pages/{}", id)))
+        .json(content))
+}
+
+#[derive(Debug, Deserialize)]
+struct ShowQuery {
+    /// Comma separated extras, `fields` puts the declaration of every field
+    /// next to its value
+    #[serde(default)]
+    include: String,
+}
+
+/// A module whose fields carry their declaration
+#[derive(Debug, Serialize)]
+struct InlineModule<'a> {
+    template: &'a str,
+    fields: BTreeMap<&'a str, InlineField<'a>>,
+    areas: BTreeMap<&'a str, Vec<InlineModule<'a>>>,
+}
+
+#[derive(Debug, Serialize)]
+struct InlineField<'a> {
+    value: &'a Value,
+    /// Missing when the template does not declare the field anymore
+    definition: Option<&'a FieldDefinition>,
+}
+
+impl<'a> InlineModule<'a> {
+    fn new(module: &'a Module, templates: &'a BTreeMap<String, TemplateDefinition>) -> Self {
+        let definition = templates.get(&module.template);
+        let fields = module
+            .fields
+            .iter()
+            .map(|(name, value)| {
+                let field = definition.and_then(|definition| definition.fields.get(name));
+                let field = InlineField {
+                    value,
+                    definition: field,
+                };
+                (name.as_str(), field)
+            })
+            .collect();
+        let areas = module
+            .areas
+            .iter()
+            .map(|(name, modules)| {
+                let modules = modules
+                    .iter()
+                    .map(|module| InlineModule::new(module, templates))
+                    .collect();
+                (name.as_str(), modules)
+            })
+            .collect();
+        Self {
+            template: &module.template,
+            fields,
+            areas,
+        }
     }
 }
 
-async fn delete_page(id: web::Path<String>, site: web::Data<Site>) -> HttpResponse {
-    match site.delete_page(&id) {
-        Ok(..) => HttpResponse::Accepted().finish(),
-        Err(error) => HttpResponse::InternalServerError().json(error.to_string()),
+#[derive(Debug, Serialize)]
+struct InlineContent<'a> {
+    #[serde(flatten)]
+    page: &'a Page,
+    module: InlineModule<'a>,
+    templates: &'a BTreeMap<String, TemplateDefinition>,
+}
+
+/// A page with its whole module tree and the definitions of its templates
+async fn show_page(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    query: web::Query<ShowQuery>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let content = site.page_content(&signed.user, &id)?;
+    if !query.include.split(',').any(|include| include == "fields") {
+        return Ok(HttpResponse::Ok().json(content));
     }
+    Ok(HttpResponse::Ok().json(InlineContent {
+        page: &content.page,
+        module: InlineModule::new(&content.module, &content.templates),
+        templates: &content.templates,
+    }))
+}
+
+async fn update_page(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    fields: web::Json<HashMap<String, Value>>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    site.update_page(&signed.user, &id, fields.into_inner())?;
+    let content = site.page_content(&signed.user, &id)?;
+    Ok(HttpResponse::Ok().json(content))
+}
+
+#[derive(Debug, Deserialize)]
+struct SlugData {
+    slug: String,
+}
+
+async fn update_slug(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    form: web::Json<SlugData>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let page = site.update_slug(&signed.user, &id, &form.slug)?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+#[derive(Debug, Deserialize)]
+struct ParentData {
+    parent: Option<String>,
+    #[serde(default)]
+    position: usize,
+}
+
+async fn move_page(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    form: web::Json<ParentData>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let page = site.move_page(&signed.user, &id, form.parent.as_deref(), form.position)?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+async fn publish_page(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let page = site.publish_page(&signed.user, &id)?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+async fn unpublish_page(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let page = site.unpublish_page(&signed.user, &id)?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/// Replace the access entries of a page, the pages under it inherit them
+async fn set_access(
+    signed: Signed<Admin>,
+    id: web::Path<String>,
+    form: web::Json<Vec<AccessEntry>>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let page = site.set_access(&signed.user, &id, form.into_inner())?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+/// Render the draft of a page for the admin
+async fn preview_page(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let html = site.preview_page(&signed.user, &id)?;
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html))
+}
+
+async fn show_page_by_slug(
+    signed: Signed<Editor>,
+    slug: web::Path<String>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let page = site
+        .get_page_by_slug(&slug)
+        .ok_or(core::Error::PageNotFound)?;
+    let page = site.page(&signed.user, &page.id)?;
+    Ok(HttpResponse::Ok().json(page))
+}
+
+#[derive(Debug, Serialize)]
+struct DeletedPage {
+    /// Fields of other pages still holding the id of the deleted page
+    dangling_references: Vec<Reference>,
+}
+
+async fn delete_page(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let dangling_references = site.delete_page(&signed.user, &id)?;
+    Ok(HttpResponse::Ok().json(DeletedPage {
+        dangling_references,
+    }))
 }
@@ -0,0 +1,80 @@
+use core::Site;
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{
+    auth::{Editor, Signed},
+    error::ApiError,
+};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let revisions = web::resource("/pages/{id}/revisions").route(web::get().to(show_revisions));
+    let revision =
+        web::resource("/pages/{id}/revisions/{number}").route(web::get().to(show_revision));
+    let diff =
+        web::resource("/pages/{id}/revisions/{from}/diff/{to}").route(web::get().to(show_diff));
+    let restore = web::resource("/pages/{id}/revisions/{number}/restore")
+        .route(web::post().to(restore_revision));
+
+    cfg.service(revisions);
+    cfg.service(revision);
+    cfg.service(diff);
+    cfg.service(restore);
+}
+
+/// A revision without its module, to list them
+#[derive(Debug, Serialize)]
+struct RevisionSummary {
+    number: usize,
+    date: DateTime<Utc>,
+    author: Option<String>,
+}
+
+async fn show_revisions(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let summaries: Vec<RevisionSummary> = site
+        .revisions(&signed.user, &id)?
+        .into_iter()
+        .map(|revision| RevisionSummary {
+            number: revision.number,
+            date: revision.date,
+            author: revision.author,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(summaries))
+}
+
+async fn show_revision(
+    signed: Signed<Editor>,
+    path: web::Path<(String, usize)>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, number) = path.into_inner();
+    let revision = site.revision(&signed.user, &id, number)?;
+    Ok(HttpResponse::Ok().json(revision))
+}
+
+async fn show_diff(
+    signed: Signed<Editor>,
+    path: web::Path<(String, usize, usize)>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, from, to) = path.into_inner();
+    let changes = site.diff_revisions(&signed.user, &id, from, to)?;
+    Ok(HttpResponse::Ok().json(changes))
+}
+
+async fn restore_revision(
+    signed: Signed<Editor>,
+    path: web::Path<(String, usize)>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, number) = path.into_inner();
+    let module = site.restore_revision(&signed.user, &id, number)?;
+    Ok(HttpResponse::Ok().json(module))
+}
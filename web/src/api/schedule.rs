@@ -0,0 +1,38 @@
+use core::Site;
+
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::{
+    auth::{Editor, Signed},
+    error::ApiError,
+};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let schedule = web::resource("/schedule").route(web::get().to(show_upcoming));
+    let pages_schedule = web::resource("/pages/{id}/schedule").route(web::put().to(schedule_page));
+
+    cfg.service(schedule);
+    cfg.service(pages_schedule);
+}
+
+async fn show_upcoming(signed: Signed<Editor>, site: web::Data<Site>) -> HttpResponse {
+    HttpResponse::Ok().json(site.upcoming_changes(&signed.user))
+}
+
+#[derive(Debug, Deserialize)]
+struct ScheduleData {
+    publish_at: Option<DateTime<Utc>>,
+    unpublish_at: Option<DateTime<Utc>>,
+}
+
+async fn schedule_page(
+    signed: Signed<Editor>,
+    id: web::Path<String>,
+    form: web::Json<ScheduleData>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let page = site.schedule_page(&signed.user, &id, form.publish_at, form.unpublish_at)?;
+    Ok(HttpResponse::Ok().json(page))
+}
@@ -0,0 +1,46 @@
+use core::{Site, TemplateKind};
+
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+use crate::{
+    auth::{Editor, Signed},
+    error::ApiError,
+};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let templates = web::resource("/templates").route(web::get().to(show_templates));
+    let templates_name = web::resource("/templates/{name:.*}").route(web::get().to(show_template));
+
+    cfg.service(templates);
+    cfg.service(templates_name);
+}
+
+#[derive(Debug, Deserialize)]
+struct TemplatesQuery {
+    /// Only list the pages or only the components
+    kind: Option<TemplateKind>,
+}
+
+/// Every template of the theme keyed by name, with its fields and areas
+async fn show_templates(
+    _: Signed<Editor>,
+    query: web::Query<TemplatesQuery>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let mut templates = site.templates()?;
+    if let Some(kind) = query.kind {
+        templates.retain(|_, template| template.kind == kind);
+    }
+    Ok(HttpResponse::Ok().json(templates))
+}
+
+/// A template from its name without the leading slash, `pages/article`
+async fn show_template(
+    _: Signed<Editor>,
+    name: web::Path<String>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let template = site.template(&format!("/{}", name))?;
+    Ok(HttpResponse::Ok().json(template))
+}
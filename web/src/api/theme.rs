@@ -0,0 +1,23 @@
+use core::Site;
+
+use actix_web::{web, HttpResponse};
+
+use crate::{
+    auth::{Developer, Signed},
+    error::ApiError,
+};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let reload = web::resource("/theme/reload").route(web::post().to(reload_theme));
+
+    cfg.service(reload);
+}
+
+/// Parse the theme files again, the previous theme stays on error
+async fn reload_theme(
+    _: Signed<Developer>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    site.reload_theme()?;
+    Ok(HttpResponse::NoContent().finish())
+}
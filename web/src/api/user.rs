@@ -0,0 +1,98 @@
+use core::{Accounts, Role, Site, UserChanges};
+
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+
+use crate::{
+    auth::{Admin, Editor, Signed},
+    error::ApiError,
+};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let login = web::resource("/login").route(web::post().to(login));
+    let logout = web::resource("/logout").route(web::post().to(logout));
+    let me = web::resource("/me").route(web::get().to(show_me));
+    let users = web::resource("/users")
+        .route(web::get().to(show_users))
+        .route(web::post().to(create_user));
+    let users_name = web::resource("/users/{name}")
+        .route(web::patch().to(update_user))
+        .route(web::delete().to(delete_user));
+
+    cfg.service(login);
+    cfg.service(logout);
+    cfg.service(me);
+    cfg.service(users);
+    cfg.service(users_name);
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginData {
+    name: String,
+    password: String,
+}
+
+async fn login(
+    form: web::Json<LoginData>,
+    accounts: web::Data<Accounts>,
+) -> Result<HttpResponse, ApiError> {
+    let session = accounts.login(&form.name, &form.password)?;
+    Ok(HttpResponse::Ok().json(session))
+}
+
+async fn logout(signed: Signed<Editor>, accounts: web::Data<Accounts>) -> HttpResponse {
+    accounts.logout(&signed.token);
+    HttpResponse::NoContent().finish()
+}
+
+async fn show_me(signed: Signed<Editor>) -> HttpResponse {
+    HttpResponse::Ok().json(signed.user)
+}
+
+async fn show_users(_: Signed<Admin>, accounts: web::Data<Accounts>) -> HttpResponse {
+    HttpResponse::Ok().json(accounts.users())
+}
+
+#[derive(Debug, Deserialize)]
+struct UserData {
+    name: String,
+    password: String,
+    role: Role,
+}
+
+async fn create_user(
+    signed: Signed<Admin>,
+    form: web::Json<UserData>,
+    accounts: web::Data<Accounts>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let user = site.create_user(
+        &accounts,
+        &signed.user,
+        &form.name,
+        &form.password,
+        form.role,
+    )?;
+    Ok(HttpResponse::Created().json(user))
+}
+
+async fn update_user(
+    signed: Signed<Admin>,
+    name: web::Path<String>,
+    form: web::Json<UserChanges>,
+    accounts: web::Data<Accounts>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    let user = site.update_user(&accounts, &signed.user, &name, form.into_inner())?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+async fn delete_user(
+    signed: Signed<Admin>,
+    name: web::Path<String>,
+    accounts: web::Data<Accounts>,
+    site: web::Data<Site>,
+) -> Result<HttpResponse, ApiError> {
+    site.delete_user(&accounts, &signed.user, &name)?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,69 @@
+use core::{Accounts, Role, User};
+use std::{
+    future::{ready, Ready},
+    marker::PhantomData,
+};
+
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+
+use crate::error::ApiError;
+
+/// Role a route requires from the user calling it
+pub trait Required {
+    const ROLE: Role;
+}
+
+pub struct Editor;
+pub struct Developer;
+pub struct Admin;
+
+impl Required for Editor {
+    const ROLE: Role = Role::Editor;
+}
+
+impl Required for Developer {
+    const ROLE: Role = Role::Developer;
+}
+
+impl Required for Admin {
+    const ROLE: Role = Role::Admin;
+}
+
+/// The user calling a route, extracted from the bearer token of the request
+/// only when its role is at least `R`
+pub struct Signed<R: Required> {
+    pub user: User,
+    pub token: String,
+    role: PhantomData<R>,
+}
+
+impl<R: Required> FromRequest for Signed<R> {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(authorize(req).map_err(ApiError::from))
+    }
+}
+
+fn authorize<R: Required>(req: &HttpRequest) -> Result<Signed<R>, core::Error> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(core::Error::Unauthenticated)?;
+    let accounts = req
+        .app_data::<web::Data<Accounts>>()
+        .ok_or(core::Error::Unauthenticated)?;
+
+    let user = accounts.authenticate(token)?;
+    if user.role < R::ROLE {
+        return Err(core::Error::Forbidden);
+    }
+    Ok(Signed {
+        user,
+        token: token.to_owned(),
+        role: PhantomData,
+    })
+}
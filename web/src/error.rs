@@ -0,0 +1,120 @@
+use core::{Diagnostic, FieldError, Reference};
+use std::fmt;
+
+use actix_web::{error::InternalError, http::StatusCode, HttpRequest, HttpResponse, ResponseError};
+use serde::Serialize;
+
+/// An error of the api, answered as `{"code", "message", "details"}` json
+/// with the status matching its cause
+#[derive(Debug)]
+pub struct ApiError(pub core::Error);
+
+impl From<core::Error> for ApiError {
+    fn from(error: core::Error) -> Self {
+        Self(error)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody<'a> {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<Details<'a>>,
+}
+
+/// What a client needs to point at the cause of the error
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum Details<'a> {
+    Fields(&'a [FieldError]),
+    Component { area: &'a str, template: &'a str },
+    Theme(&'a [Diagnostic]),
+    References(&'a [Reference]),
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        use core::Error::*;
+        match self.0 {
+            PageNotFound | TemplateNotFound | RevisionNotFound | ModuleNotFound | UserNotFound
+            | PageNotPublished | MediaNotFound => StatusCode::NOT_FOUND,
+            DuplicatedName
+            | DuplicatedSlug
+            | DuplicatedUser
+            | PageHasChildren
+            | LastAdmin
+            | MediaInUse(..)
+            | OutputCollision { .. } => StatusCode::CONFLICT,
+            Validation(..)
+            | InvalidSlug
+            | EmptyPageName
+            | PageCycle
+            | InvalidSchedule
+            | AreaNotFound
+            | ComponentNotAccepted { .. }
+            | EmptyUserName
+            | EmptyPassword
+            | EmptyFile
+            | InvalidTheme(..) => StatusCode::UNPROCESSABLE_ENTITY,
+            InvalidModulePath => StatusCode::BAD_REQUEST,
+            UnsupportedMedia => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            InvalidCredentials | Unauthenticated => StatusCode::UNAUTHORIZED,
+            Forbidden => StatusCode::FORBIDDEN,
+            TooManyAttempts => StatusCode::TOO_MANY_REQUESTS,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        if status.is_server_error() {
+            log::error!("{}", self.0);
+        }
+        let details = match &self.0 {
+            core::Error::Validation(errors) => Some(Details::Fields(errors)),
+            core::Error::ComponentNotAccepted { area, template } => {
+                Some(Details::Component { area, template })
+            }
+            core::Error::InvalidTheme(diagnostics) => Some(Details::Theme(diagnostics)),
+            core::Error::MediaInUse(references) => Some(Details::References(references)),
+            _ => None,
+        };
+        HttpResponse::build(status).json(ErrorBody {
+            code: self.0.code(),
+            message: self.0.to_string(),
+            details,
+        })
+    }
+}
+
+/// Answer a body, query or path the api could not read with the same json
+/// as the other errors
+pub fn invalid_request<E>(error: E, _: &HttpRequest) -> actix_web::Error
+where
+    E: fmt::Display + fmt::Debug + 'static,
+{
+    let response = HttpResponse::BadRequest().json(ErrorBody {
+        code: "invalid_request",
+        message: error.to_string(),
+        details: None,
+    });
+    InternalError::from_response(error, response).into()
+}
+
+/// Answer an upload bigger than the api accepts
+pub fn too_large(limit: usize) -> actix_web::Error {
+    let message = format!("upload is bigger than {} bytes", limit);
+    let response = HttpResponse::PayloadTooLarge().json(ErrorBody {
+        code: "too_large",
+        message: message.to_owned(),
+        details: None,
+    });
+    InternalError::from_response(message, response).into()
+}
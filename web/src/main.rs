@@ -1,37 +1,163 @@
 use actix_files::Files;
-use actix_web::{middleware::Logger, web, App, HttpServer};
+use actix_web::{
+    http::header::{self, DispositionType},
+    middleware::{DefaultHeaders, Logger},
+    web, App, HttpServer,
+};
 
-use core::SiteBuilder;
+use core::{Accounts, SiteBuilder};
 
 mod api;
+mod auth;
+mod error;
+mod public;
+mod schedule;
+mod watch;
+
+/// Folder receiving the static build of the site
+pub const OUTPUT_DIR: &str = "./public";
+const STORAGE_FILE: &str = "./core/tests/test_site/yaml_storage.yml";
+const THEME_DIR: &str = "./core/tests/test_site/theme";
+/// Folder of the media library when `JILO_MEDIA_DIR` is not set
+const DEFAULT_MEDIA_DIR: &str = "./core/tests/test_site/media";
+const USERS_FILE: &str = "./core/tests/test_site/users.yml";
+/// File receiving the generated password of the first admin, readable by the
+/// owner only
+const ADMIN_PASSWORD_FILE: &str = "./core/tests/test_site/admin_password.txt";
+/// Name of the admin created when there is no account yet
+const INITIAL_ADMIN: &str = "admin";
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
+    if std::env::args().nth(1).as_deref() == Some("check-theme") {
+        let path = std::env::args()
+            .nth(2)
+            .unwrap_or_else(|| THEME_DIR.to_string());
+        check_theme(&path);
+    }
+
     log::info!("starting HTTP server at http://localhost:8080");
 
+    let media_dir =
+        std::env::var("JILO_MEDIA_DIR").unwrap_or_else(|_| DEFAULT_MEDIA_DIR.to_string());
     let site = SiteBuilder::new()
         .add_tera_renderer()
         .unwrap()
-        .add_yaml_storage("./core/tests/test_site/yaml_storage.yml")
-        .unwrap()
-        .add_theme("./core/tests/test_site/theme")
+        .add_yaml_storage(STORAGE_FILE)
         .unwrap()
-        .build();
+        .add_theme(THEME_DIR)
+        .and_then(|builder| builder.add_media(&media_dir))
+        .and_then(SiteBuilder::build)
+        .unwrap_or_else(|error| {
+            log::error!("{}", error);
+            std::process::exit(1)
+        });
 
+    let accounts = Accounts::load(USERS_FILE).unwrap_or_else(|error| {
+        log::error!("could not load users: {}", error);
+        std::process::exit(1)
+    });
+    // A generated password is written to a file, never to the logs
+    let password = std::env::var("JILO_ADMIN_PASSWORD").ok();
+    let keep = |password: &str| Ok(write_secret(ADMIN_PASSWORD_FILE, password)?);
+    match accounts.ensure_admin(INITIAL_ADMIN, password.as_deref(), keep) {
+        Ok(Some(..)) if password.is_some() => log::info!("created user {}", INITIAL_ADMIN),
+        Ok(Some(..)) => log::warn!(
+            "created user {} with the password written in {}, change it once logged in",
+            INITIAL_ADMIN,
+            ADMIN_PASSWORD_FILE
+        ),
+        Ok(None) => {}
+        Err(error) => log::error!(
+            "could not create the first admin, JILO_ADMIN_PASSWORD can set its password: {}",
+            error
+        ),
+    }
+
+    let media_files = site.media_folder().unwrap_or_else(|| media_dir.into());
     let data = web::Data::new(site);
+    let accounts = web::Data::new(accounts);
+
+    schedule::start_scheduler(data.clone());
+
+    if std::env::args().any(|arg| arg == "--watch") {
+        if let Err(error) = watch::watch_theme(data.clone()) {
+            log::error!("could not watch theme: {}", error);
+        }
+    }
 
     HttpServer::new(move || {
-        let public_scope = Files::new("/", "dist/").index_file("index.html");
+        let admin_scope = Files::new("/admin", "dist/").index_file("index.html");
+        // Uploads share the origin of the admin, so browsers must never guess
+        // their type and only images are shown in place
+        let media = Files::new("", &media_files).mime_override(|name| match name.as_str() {
+            "image" => DispositionType::Inline,
+            _ => DispositionType::Attachment,
+        });
+        let media_scope = web::scope("/media")
+            .wrap(DefaultHeaders::new().add((header::X_CONTENT_TYPE_OPTIONS, "nosniff")))
+            .service(media);
 
         App::new()
             .app_data(data.clone())
+            .app_data(accounts.clone())
             .configure(api::config)
-            .service(public_scope)
+            .service(admin_scope)
+            .service(media_scope)
+            .configure(public::config)
             .wrap(Logger::default())
     })
     .bind(("127.0.0.1", 8080))?
     .run()
     .await
 }
+
+/// Write a secret to a new file only its owner can read, never over an
+/// existing one
+fn write_secret(path: &str, secret: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    std::io::Write::write_all(&mut file, format!("{}\n", secret).as_bytes())
+}
+
+/// Report every problem of a theme and exit, with a failure code if any is
+/// an error
+fn check_theme(path: &str) -> ! {
+    match core::check_theme(path) {
+        Ok(diagnostics) if diagnostics.is_empty() => {
+            log::info!("theme {} has no problem", path);
+            std::process::exit(0)
+        }
+        Ok(diagnostics) => {
+            for diagnostic in &diagnostics {
+                if diagnostic.is_error() {
+                    log::error!("{}", diagnostic);
+                } else {
+                    log::warn!("{}", diagnostic);
+                }
+            }
+            let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+            let warnings = diagnostics.len() - errors;
+            if errors > 0 {
+                log::error!(
+                    "theme {} has {} error(s) and {} warning(s)",
+                    path,
+                    errors,
+                    warnings
+                );
+                std::process::exit(1)
+            }
+            log::warn!("theme {} has {} warning(s)", path, warnings);
+            std::process::exit(0)
+        }
+        Err(error) => {
+            log::error!("could not check theme {}: {}", path, error);
+            std::process::exit(1)
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use core::{PageStatus, Site};
+
+use actix_files::NamedFile;
+use actix_web::{http::header::ContentType, web, HttpRequest, HttpResponse};
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let pages = web::resource("/{path:.*}").route(web::get().to(serve_page));
+
+    cfg.service(pages);
+}
+
+/// Serve the published page found at the path, from the static build while
+/// it is current, rendered on the fly otherwise
+async fn serve_page(
+    req: HttpRequest,
+    path: web::Path<String>,
+    site: web::Data<Site>,
+) -> HttpResponse {
+    let page = match site.find_page(&path) {
+        Some(page) if page.status != PageStatus::Draft => page,
+        _ => return not_found(&site),
+    };
+
+    if let Some(built) = site.built_file(&page.id) {
+        if let Ok(file) = NamedFile::open_async(built).await {
+            return file.into_response(&req);
+        }
+    }
+
+    match site.render_page(&page.name) {
+        Ok(html) => html_response(html),
+        Err(error) => {
+            log::error!("could not render page {}: {}", page.id, error);
+            HttpResponse::InternalServerError()
+                .content_type(ContentType::plaintext())
+                .body(error.to_string())
+        }
+    }
+}
+
+fn html_response(html: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html)
+}
+
+fn not_found(site: &Site) -> HttpResponse {
+    let html = site.render_not_found().unwrap_or_else(|error| {
+        log::error!("could not render not found page: {}", error);
+        String::from("Page not found")
+    });
+    HttpResponse::NotFound()
+        .content_type(ContentType::html())
+        .body(html)
+}
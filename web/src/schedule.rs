@@ -0,0 +1,35 @@
+use core::Site;
+use std::{path::Path, thread, time::Duration};
+
+use actix_web::web;
+
+use crate::OUTPUT_DIR;
+
+/// Time between two checks of the pages schedule
+const INTERVAL: Duration = Duration::from_secs(30);
+
+/// Publish and unpublish the scheduled pages when their time comes, then
+/// build the site again so the static output follows
+pub fn start_scheduler(site: web::Data<Site>) {
+    thread::spawn(move || loop {
+        match site.apply_schedule() {
+            Ok(changes) if changes.is_empty() => {}
+            Ok(changes) => {
+                for change in &changes {
+                    log::info!("{:?} page {} as scheduled", change.action, change.name);
+                }
+                match site.build(Path::new(OUTPUT_DIR)) {
+                    Ok(report) if report.is_success() => log::info!("site rebuilt"),
+                    Ok(report) => {
+                        for failure in &report.failures {
+                            log::error!("could not build page {}: {}", failure.name, failure.error);
+                        }
+                    }
+                    Err(error) => log::error!("could not build site: {}", error),
+                }
+            }
+            Err(error) => log::error!("could not apply schedule: {}", error),
+        }
+        thread::sleep(INTERVAL);
+    });
+}
@@ -0,0 +1,36 @@
+use core::Site;
+use std::{path::PathBuf, sync::mpsc, thread, time::Duration};
+
+use actix_web::web;
+use notify::{RecursiveMode, Watcher};
+
+/// Time left to an editor to finish writing its files before reloading
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Reload the theme of the site every time a file of its folder changes
+pub fn watch_theme(site: web::Data<Site>) -> notify::Result<()> {
+    let folder: PathBuf = site.theme_folder();
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(&folder, RecursiveMode::Recursive)?;
+
+    log::info!("watching theme {}", folder.display());
+    thread::spawn(move || {
+        // The watcher stops as soon as it is dropped
+        let _watcher = watcher;
+        while let Ok(event) = receiver.recv() {
+            if let Err(error) = event {
+                log::warn!("theme watch error: {}", error);
+                continue;
+            }
+            while receiver.recv_timeout(DEBOUNCE).is_ok() {}
+
+            match site.reload_theme() {
+                Ok(()) => log::info!("theme reloaded"),
+                Err(error) => log::error!("theme not reloaded, {}", error),
+            }
+        }
+    });
+
+    Ok(())
+}